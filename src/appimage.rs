@@ -1,17 +1,115 @@
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use std::{
-    path::PathBuf,
+    collections::BTreeMap,
+    path::{Path, PathBuf},
     process::{Command, Stdio},
 };
 use tokio::fs;
 
-use crate::{Error, InstallArgs, Result, desktops_dir, icons_dir};
+use crate::{
+    Config, DESKTOP_ENTRY_GROUP, DesktopEntry, ElfInfo, Error, INDEX_SCHEMA_VERSION, InstallArgs,
+    Metainfo, Recipe, Result, SandboxBackend, SandboxProfile, icon_name, in_path, install_icons,
+    install_mime_packages, launcher_path, make_temp_dir, parse_github_url,
+    refresh_desktop_database, remove_default_handler, remove_icons, remove_launcher,
+    remove_mime_packages, set_default_handler, write_launcher,
+};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct AppImage {
+    /// Layout of the index entry, older ones are migrated when they're read
+    #[serde(default)]
+    pub schema_version: u32,
     pub file_path: PathBuf,
     pub executable: String,
     pub source: Source,
+    #[serde(default)]
+    pub sha256: Option<String>,
+    #[serde(default)]
+    pub integration: Option<Integration>,
+    #[serde(default)]
+    pub version: Option<String>,
+    #[serde(default)]
+    pub metainfo: Option<Metainfo>,
+    #[serde(default)]
+    pub installed_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub updated_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub run_mode: RunMode,
+    /// Environment variables the launcher sets before starting the app
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+    /// Arguments the launcher passes before the ones given on the command line
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// More names the app can be started by, besides the executable
+    #[serde(default)]
+    pub aliases: Vec<String>,
+    #[serde(default)]
+    pub symlinks: Vec<PathBuf>,
+    #[serde(default)]
+    pub portable: Portable,
+    #[serde(default)]
+    pub sandbox: Option<SandboxProfile>,
+}
+
+/// What's in an app's portable directory, each one only once it's been enabled.
+pub const PORTABLE_DIRS: [&str; 2] = ["home", "config"];
+
+/// Which of the app's own directories replace the user's, like `--appimage-portable-home`
+/// and `--appimage-portable-config` do.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Portable {
+    pub home: bool,
+    pub config: bool,
+}
+
+impl Portable {
+    pub fn is_enabled(&self) -> bool {
+        self.home || self.config
+    }
+}
+
+/// How the command starts the app.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum RunMode {
+    /// Run the AppImage itself, which mounts its filesystem with FUSE
+    #[default]
+    Fuse,
+    /// Run the AppRun of an AppDir extracted once at install time
+    Extract,
+    /// Have the AppImage extract itself to a temporary directory on every start
+    ExtractAndRun,
+}
+
+impl std::fmt::Display for RunMode {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            RunMode::Fuse => write!(fmt, "fuse"),
+            RunMode::Extract => write!(fmt, "extract"),
+            RunMode::ExtractAndRun => write!(fmt, "extract-and-run"),
+        }
+    }
+}
+
+/// Everything desktop integration put on disk, so it can be redone or undone later.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Integration {
+    pub desktop_files: Vec<PathBuf>,
+    pub icons: Vec<PathBuf>,
+    pub mime_packages: Vec<PathBuf>,
+    pub default_handler: bool,
+}
+
+impl Integration {
+    pub fn files(&self) -> impl Iterator<Item = &PathBuf> {
+        self.desktop_files
+            .iter()
+            .chain(&self.icons)
+            .chain(&self.mime_packages)
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -23,161 +121,647 @@ pub struct Source {
 #[derive(Debug, Serialize, Deserialize)]
 pub struct SourceMetadata {
     pub url: String,
+    #[serde(default)]
+    pub tag: Option<String>,
+    #[serde(default)]
+    pub asset_pattern: Option<String>,
+    #[serde(default)]
+    pub latest: bool,
+    /// The tag was asked for, so updates stay on it
+    #[serde(default)]
+    pub pinned: bool,
+}
+
+impl SourceMetadata {
+    pub fn new(url: &str) -> Self {
+        Self {
+            url: url.to_string(),
+            tag: None,
+            asset_pattern: None,
+            latest: false,
+            pinned: false,
+        }
+    }
+}
+
+impl Source {
+    pub fn is_updatable(&self) -> bool {
+        self.identifier == "git.github"
+    }
+}
+
+/// Resolves `--from` to a local file if it's a `file://` URL or a plain path.
+fn local_source(from: &str) -> Option<PathBuf> {
+    let path = if from.starts_with("file://") {
+        reqwest::Url::parse(from).ok()?.to_file_path().ok()?
+    } else if from.contains("://") {
+        return None;
+    } else {
+        PathBuf::from(from)
+    };
+
+    Some(std::fs::canonicalize(&path).unwrap_or(path))
 }
 
 impl AppImage {
     pub fn new(options: &InstallArgs) -> Self {
-        Self {
-            file_path: PathBuf::new(),
-            executable: options
+        Self::from_source(
+            options.from.as_deref().unwrap_or(&options.appname),
+            options.github,
+            options
                 .executable
                 .as_ref()
                 .unwrap_or(&options.appname)
                 .to_string(),
-            source: Source {
-                identifier: if options.github {
-                    "git.github".to_string()
-                } else {
-                    "raw_url".to_string()
-                },
+        )
+    }
+    pub fn from_recipe(recipe: &Recipe, options: &InstallArgs) -> Self {
+        let mut appimage = Self::from_source(
+            &recipe.source,
+            recipe.github,
+            options
+                .executable
+                .as_ref()
+                .or(recipe.executable.as_ref())
+                .unwrap_or(&options.appname)
+                .to_string(),
+        );
+
+        if recipe.asset_pattern.is_some() {
+            appimage.source.meta.asset_pattern = recipe.asset_pattern.clone();
+        }
+
+        appimage
+    }
+    fn from_source(from: &str, github: bool, executable: String) -> Self {
+        let source = if github {
+            Source {
+                identifier: "git.github".to_string(),
+                meta: SourceMetadata::new(from),
+            }
+        } else if let Some(github_url) = parse_github_url(from) {
+            Source {
+                identifier: "git.github".to_string(),
                 meta: SourceMetadata {
-                    url: options.from.clone(),
+                    url: github_url.slug,
+                    pinned: github_url.tag.is_some(),
+                    tag: github_url.tag,
+                    asset_pattern: github_url.asset_pattern,
+                    latest: github_url.latest,
                 },
-            },
+            }
+        } else if let Some(path) = local_source(from) {
+            Source {
+                identifier: "local".to_string(),
+                meta: SourceMetadata::new(&path.to_string_lossy()),
+            }
+        } else {
+            Source {
+                identifier: "raw_url".to_string(),
+                meta: SourceMetadata::new(from),
+            }
+        };
+
+        Self {
+            schema_version: INDEX_SCHEMA_VERSION,
+            file_path: PathBuf::new(),
+            executable,
+            source,
+            sha256: None,
+            integration: None,
+            version: None,
+            metainfo: None,
+            installed_at: None,
+            updated_at: None,
+            run_mode: RunMode::default(),
+            env: BTreeMap::new(),
+            args: vec![],
+            aliases: vec![],
+            symlinks: vec![],
+            portable: Portable::default(),
+            sandbox: None,
         }
     }
-    async fn extract_assets(&self) -> Result<PathBuf> {
-        let temp_dir = std::env::temp_dir().join("zap-rs");
+    /// Extracts what matches `patterns` to `squashfs-root` in a new temporary directory,
+    /// which the caller removes.
+    async fn extract(&self, patterns: &[&str]) -> Result<PathBuf> {
+        // Reading the filesystem image directly means the AppImage is never run before the user does
+        if !in_path("unsquashfs") {
+            return Err(Error::UnsquashfsUnavailable);
+        }
+        let offset = ElfInfo::read(&self.file_path).await?.end;
 
-        fs::create_dir_all(&temp_dir).await?;
+        // Private to this run, another one or another user can't get in the way
+        let temp_dir = make_temp_dir(&std::env::temp_dir())?;
 
-        // Extract desktop file
-        Command::new(&self.file_path)
-            .arg("--appimage-extract")
-            .arg("*.desktop")
-            .current_dir(&temp_dir)
-            .stdout(Stdio::null())
-            .spawn()?
-            .wait()?;
-        Command::new(&self.file_path)
-            .arg("--appimage-extract")
-            .arg("usr/share/applications/*.desktop")
-            .current_dir(&temp_dir)
+        // Not every pattern matches in every AppImage, whatever did is what there is to go by
+        Command::new("unsquashfs")
+            .arg("-o")
+            .arg(offset.to_string())
+            .args(["-no-xattrs", "-d"])
+            .arg(temp_dir.join("squashfs-root"))
+            .arg(&self.file_path)
+            .args(patterns)
             .stdout(Stdio::null())
-            .spawn()?
-            .wait()?;
+            .stderr(Stdio::null())
+            .status()?;
+        fs::create_dir_all(temp_dir.join("squashfs-root")).await?;
+
+        Ok(temp_dir)
+    }
+    pub fn appdir_path(&self, config: &Config) -> PathBuf {
+        config.appdirs_dir().join(&self.executable)
+    }
+    /// What the launcher, if any, ends up running.
+    fn target_path(&self, config: &Config) -> PathBuf {
+        match self.run_mode {
+            RunMode::Fuse | RunMode::ExtractAndRun => self.file_path.clone(),
+            RunMode::Extract => self.appdir_path(config).join("AppRun"),
+        }
+    }
+    fn needs_launcher(&self) -> bool {
+        self.run_mode == RunMode::ExtractAndRun
+            || !self.env.is_empty()
+            || !self.args.is_empty()
+            || self.portable.is_enabled()
+            || self.sandbox.is_some()
+    }
+    /// Kept apart from the AppImage, whose file name changes with the download URL.
+    pub fn portable_path(&self, config: &Config) -> PathBuf {
+        config.portable_dir().join(&self.executable)
+    }
+    pub fn portable_home_path(&self, config: &Config) -> PathBuf {
+        self.portable_path(config).join("home")
+    }
+    pub fn portable_config_path(&self, config: &Config) -> PathBuf {
+        self.portable_path(config).join("config")
+    }
+    /// Creates the enabled portable directories, adopting any `<file>.home` or
+    /// `<file>.config` the AppImage runtime made next to the file.
+    async fn prepare_portable(&self, config: &Config) -> Result<()> {
+        let dirs = [
+            (self.portable.home, self.portable_home_path(config), "home"),
+            (
+                self.portable.config,
+                self.portable_config_path(config),
+                "config",
+            ),
+        ];
+
+        for (enabled, path, extension) in dirs {
+            if !enabled || fs::try_exists(&path).await? {
+                continue;
+            }
+
+            let mut runtime_path = self.file_path.clone().into_os_string();
+            runtime_path.push(format!(".{extension}"));
+            let runtime_path = PathBuf::from(runtime_path);
+
+            fs::create_dir_all(self.portable_path(config)).await?;
+            if fs::metadata(&runtime_path)
+                .await
+                .is_ok_and(|metadata| metadata.is_dir())
+            {
+                fs::rename(runtime_path, &path).await?;
+            } else {
+                fs::create_dir_all(&path).await?;
+            }
+        }
+
+        Ok(())
+    }
+    /// Portable directories the app left behind, e.g. from before it was removed.
+    pub async fn find_portable(&self, config: &Config) -> Result<Portable> {
+        Ok(Portable {
+            home: fs::try_exists(self.portable_home_path(config)).await?,
+            config: fs::try_exists(self.portable_config_path(config)).await?,
+        })
+    }
+    pub async fn purge_portable(&self, config: &Config) -> Result<()> {
+        let path = self.portable_path(config);
+
+        if fs::try_exists(&path).await? {
+            fs::remove_dir_all(path).await?;
+        }
+
+        Ok(())
+    }
+    /// Every name the app gets a command for.
+    pub fn commands(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(self.executable.as_str()).chain(self.aliases.iter().map(String::as_str))
+    }
+    /// Everything a symlink of the app may point at, whichever run mode it was made in.
+    pub fn link_targets(&self, config: &Config) -> [PathBuf; 3] {
+        [
+            self.file_path.clone(),
+            self.appdir_path(config).join("AppRun"),
+            launcher_path(config, &self.executable),
+        ]
+    }
+    /// Every file and directory zap-rs created for the app.
+    pub fn owned_files(&self, config: &Config) -> Vec<PathBuf> {
+        let mut files = vec![self.file_path.clone()];
+
+        files.extend(self.symlinks.iter().cloned());
+        if self.needs_launcher() {
+            files.push(launcher_path(config, &self.executable));
+        }
+        if self.run_mode == RunMode::Extract {
+            files.push(self.appdir_path(config));
+        }
+        if let Some(integration) = &self.integration {
+            files.extend(integration.files().cloned());
+        }
+        if self.portable.is_enabled() {
+            files.push(self.portable_path(config));
+        }
+
+        files
+    }
+    /// What the command and desktop entries should run, depending on the run mode.
+    pub fn command_path(&self, config: &Config) -> PathBuf {
+        if self.needs_launcher() {
+            launcher_path(config, &self.executable)
+        } else {
+            self.target_path(config)
+        }
+    }
+    /// Replaces the AppDir with a fresh copy of the AppImage's contents.
+    pub async fn extract_appdir(&self, config: &Config) -> Result<()> {
+        let appdirs_dir = config.appdirs_dir();
+        let squashfs = appdirs_dir.join("squashfs-root");
 
-        // Extract icon
-        Command::new(&self.file_path)
+        if fs::try_exists(&squashfs).await? {
+            fs::remove_dir_all(&squashfs).await?;
+        }
+        fs::create_dir_all(&appdirs_dir).await?;
+
+        // Extracting right next to the destination keeps the rename on one filesystem
+        let status = Command::new(&self.file_path)
             .arg("--appimage-extract")
-            .arg("usr/share/icons/hicolor/*/apps/*.png")
-            .current_dir(&temp_dir)
+            .current_dir(&appdirs_dir)
             .stdout(Stdio::null())
-            .spawn()?
-            .wait()?;
+            .status()?;
 
-        Ok(temp_dir)
+        if !status.success() || !fs::try_exists(squashfs.join("AppRun")).await? {
+            if fs::try_exists(&squashfs).await? {
+                fs::remove_dir_all(&squashfs).await?;
+            }
+            return Err(Error::InvalidAppImage);
+        }
+
+        let appdir = self.appdir_path(config);
+        if fs::try_exists(&appdir).await? {
+            fs::remove_dir_all(&appdir).await?;
+        }
+        fs::rename(&squashfs, appdir).await?;
+
+        Ok(())
     }
-    async fn fix_desktop(&self, desktop_file_path: &PathBuf, icon_found: bool) -> Result<()> {
-        let file_content = fs::read_to_string(&desktop_file_path).await?;
+    /// Puts whatever the run mode, environment and arguments need besides the AppImage in
+    /// place, then drops what's left over, so a failure leaves the previous setup working.
+    pub async fn prepare_run_mode(&self, config: &Config) -> Result<()> {
+        // Changing the environment or arguments only needs a new launcher, not a new AppDir
+        if self.run_mode == RunMode::Extract
+            && !fs::try_exists(self.appdir_path(config).join("AppRun")).await?
+        {
+            self.extract_appdir(config).await?;
+        }
+        self.prepare_portable(config).await?;
+
+        if self.needs_launcher() {
+            let portable_home = self
+                .portable_home_path(config)
+                .to_string_lossy()
+                .to_string();
+            let portable_config = self
+                .portable_config_path(config)
+                .to_string_lossy()
+                .to_string();
 
-        let appimage_path = self.file_path.to_str().ok_or(Error::InvalidPath)?;
-
-        let icon_path = icons_dir()?
-            .join(format!("{}.png", self.executable))
-            .to_str()
-            .ok_or(Error::InvalidPath)?
-            .to_string();
-
-        let fixed_file_content: Vec<String> = file_content
-            .lines()
-            .map(|line| {
-                if line.contains("Exec=") {
-                    if let Some(exec_line) = line.split_once(" ") {
-                        if let Some(exec_arg) = exec_line.0.split_once("=") {
-                            format!("{}={} {}", exec_arg.0, appimage_path, exec_line.1)
-                        } else {
-                            line.to_string()
-                        }
-                    } else if let Some(exec_arg) = line.split_once("=") {
-                        format!("{}={}", exec_arg.0, appimage_path)
-                    } else {
-                        line.to_string()
+            let mut env: Vec<(&str, &str)> = self
+                .env
+                .iter()
+                .map(|(key, value)| (key.as_str(), value.as_str()))
+                .collect();
+            if self.run_mode == RunMode::ExtractAndRun {
+                env.push(("APPIMAGE_EXTRACT_AND_RUN", "1"));
+            }
+            // The same variables the runtime sets for portable directories
+            if self.portable.home {
+                env.push(("HOME", &portable_home));
+            }
+            if self.portable.config {
+                env.push(("XDG_CONFIG_HOME", &portable_config));
+            }
+
+            let target = self.target_path(config);
+            let mut command = match &self.sandbox {
+                Some(sandbox) => {
+                    if !in_path(sandbox.backend.program()) {
+                        return Err(Error::SandboxUnavailable(
+                            sandbox.backend.program().to_string(),
+                        ));
                     }
-                } else if line.contains("Icon=") && icon_found {
-                    if let Some(exec_arg) = line.split_once("=") {
-                        format!("{}={}", exec_arg.0, icon_path)
-                    } else {
-                        line.to_string()
+
+                    // FUSE doesn't work inside bubblewrap, firejail mounts the AppImage itself
+                    let fuse = self.run_mode == RunMode::Fuse;
+                    if fuse && sandbox.backend == SandboxBackend::Bubblewrap {
+                        env.push(("APPIMAGE_EXTRACT_AND_RUN", "1"));
                     }
-                } else {
-                    line.to_string()
+
+                    let app_path = match self.run_mode {
+                        RunMode::Extract => self.appdir_path(config),
+                        _ => self.file_path.clone(),
+                    };
+                    let data_paths: Vec<PathBuf> = [
+                        (self.portable.home, self.portable_home_path(config)),
+                        (self.portable.config, self.portable_config_path(config)),
+                    ]
+                    .into_iter()
+                    .filter_map(|(enabled, path)| enabled.then_some(path))
+                    .collect();
+
+                    sandbox.command(&config.dirs.home, &target, &[app_path], &data_paths, fuse)
                 }
-            })
-            .collect();
+                None => vec![target.to_string_lossy().to_string()],
+            };
+            command.extend(self.args.iter().cloned());
 
-        fs::write(desktop_file_path, fixed_file_content.join("\n")).await?;
+            write_launcher(config, &self.executable, &command, &env).await?;
+        } else {
+            remove_launcher(config, &self.executable).await?;
+        }
+
+        let appdir = self.appdir_path(config);
+        if self.run_mode != RunMode::Extract && fs::try_exists(&appdir).await? {
+            fs::remove_dir_all(appdir).await?;
+        }
 
         Ok(())
     }
-    pub async fn integrate_desktop(&self) -> Result<()> {
-        let temp_dir = self.extract_assets().await?;
-        let squashfs = &temp_dir.join("squashfs-root");
+    pub async fn cleanup_run_mode(&self, config: &Config) -> Result<()> {
+        let appdir = self.appdir_path(config);
+        if fs::try_exists(&appdir).await? {
+            fs::remove_dir_all(appdir).await?;
+        }
 
-        fs::create_dir_all(desktops_dir()?).await?;
-        fs::create_dir_all(icons_dir()?).await?;
-        fs::create_dir_all(
-            PathBuf::from(std::env::var("HOME")?).join(".local/share/applications/"),
-        )
-        .await?;
+        remove_launcher(config, &self.executable).await
+    }
+    async fn extract_assets(&self) -> Result<PathBuf> {
+        self.extract(&[
+            // Desktop files
+            "*.desktop",
+            "usr/share/applications/*.desktop",
+            // Icons and MIME packages
+            "usr/share/mime/packages/*.xml",
+            "usr/share/icons/hicolor/*/apps/*",
+            "usr/share/pixmaps/*",
+            ".DirIcon",
+            "*.png",
+            "*.svg",
+            "*.xpm",
+        ])
+        .await
+    }
+    pub async fn read_metainfo(&self) -> Result<Option<Metainfo>> {
+        let temp_dir = self
+            .extract(&["usr/share/metainfo/*.xml", "usr/share/appdata/*.xml"])
+            .await?;
 
-        let icon_path = icons_dir()?.join(format!("{}.png", self.executable));
-        let desktop_file_paths = (
-            desktops_dir()?.join(format!("{}.desktop", self.executable)),
-            PathBuf::from(std::env::var("HOME")?).join(format!(
-                ".local/share/applications/{}.desktop",
-                self.executable
-            )),
-        );
+        let metainfo = Metainfo::find(&temp_dir.join("squashfs-root")).await;
 
-        let icon_resolutions = [
-            "1024", "720", "512", "256", "192", "128", "96", "72", "64", "48", "36", "32", "24",
-            "22", "16",
-        ];
+        fs::remove_dir_all(temp_dir).await?;
 
-        let mut icon_found = false;
+        metainfo
+    }
+    /// Picks up the metainfo of a freshly downloaded file, preferring the source's version.
+    pub async fn refresh_metadata(&mut self) {
+        // Metainfo is a nice to have, an AppImage that can't be extracted shouldn't fail the install
+        self.metainfo = self.read_metainfo().await.ok().flatten();
+        self.version = self.source.meta.tag.clone().or_else(|| {
+            self.metainfo
+                .as_ref()
+                .and_then(|metainfo| metainfo.version.clone())
+        });
+    }
+    async fn fix_desktop(
+        &self,
+        config: &Config,
+        desktop_file_path: &PathBuf,
+        icon_name: Option<&str>,
+    ) -> Result<()> {
+        let file_content = fs::read_to_string(&desktop_file_path).await?;
+        let mut desktop_entry = DesktopEntry::parse(&file_content)?;
 
-        for res in icon_resolutions {
-            let icon_dir = squashfs.join(format!("usr/share/icons/hicolor/{}x{}/apps", res, res));
+        let command_path = self.command_path(config);
+        let exec_path = command_path.to_str().ok_or(Error::InvalidPath)?;
 
-            if fs::try_exists(&icon_dir).await? {
-                let mut squashfs_icon_entries = fs::read_dir(&icon_dir).await?;
-                while let Some(entry) = squashfs_icon_entries.next_entry().await? {
-                    if entry.path().extension() == Some("png".as_ref()) {
-                        fs::copy(entry.path(), &icon_path).await?;
-                        icon_found = true;
-                        break;
-                    }
-                }
-                if icon_found {
-                    break;
-                }
+        for group in desktop_entry.groups_mut() {
+            if group.name != DESKTOP_ENTRY_GROUP && !group.is_action() {
+                continue;
+            }
+
+            group.set_exec_program(exec_path)?;
+
+            if group.contains("TryExec") {
+                group.set_all_locales("TryExec", exec_path);
+            }
+            if let Some(icon_name) = icon_name {
+                group.set_all_locales("Icon", icon_name);
             }
         }
 
+        desktop_entry.validate()?;
+
+        fs::write(desktop_file_path, desktop_entry.to_string()).await?;
+
+        Ok(())
+    }
+    pub async fn integrate_desktop(
+        &self,
+        config: &Config,
+        default_handler: bool,
+    ) -> Result<Integration> {
+        let temp_dir = self.extract_assets().await?;
+        let integration = self
+            .integrate_from(config, &temp_dir.join("squashfs-root"), default_handler)
+            .await;
+
+        // Clean up
+        fs::remove_dir_all(temp_dir).await?;
+
+        integration
+    }
+    async fn integrate_from(
+        &self,
+        config: &Config,
+        squashfs: &Path,
+        default_handler: bool,
+    ) -> Result<Integration> {
+        fs::create_dir_all(config.desktops_dir()).await?;
+        fs::create_dir_all(config.applications_dir()).await?;
+
+        let desktop_file_paths = (
+            config
+                .desktops_dir()
+                .join(format!("{}.desktop", self.executable)),
+            config
+                .applications_dir()
+                .join(format!("{}.desktop", self.executable)),
+        );
+
+        let mut integration = Integration {
+            default_handler,
+            ..Default::default()
+        };
+
         let mut squashfs_entries = fs::read_dir(&squashfs).await?;
         while let Some(entry) = squashfs_entries.next_entry().await? {
             if entry.path().extension() == Some("desktop".as_ref()) {
                 fs::copy(fs::canonicalize(entry.path()).await?, &desktop_file_paths.0).await?;
 
-                self.fix_desktop(&desktop_file_paths.0, icon_found).await?;
+                let desktop_entry =
+                    DesktopEntry::parse(&fs::read_to_string(&desktop_file_paths.0).await?)?;
+                let icon = desktop_entry
+                    .main_group()
+                    .and_then(|group| group.get("Icon"));
+
+                let installed_icons =
+                    install_icons(config, squashfs, icon.as_deref(), &self.executable).await?;
+                let icon_name = icon_name(&self.executable);
+
+                self.fix_desktop(
+                    config,
+                    &desktop_file_paths.0,
+                    (!installed_icons.is_empty()).then_some(icon_name.as_str()),
+                )
+                .await?;
 
                 fs::copy(&desktop_file_paths.0, &desktop_file_paths.1).await?;
+
+                integration.desktop_files = vec![
+                    desktop_file_paths.0.to_path_buf(),
+                    desktop_file_paths.1.to_path_buf(),
+                ];
+                integration.icons = installed_icons;
+
+                if default_handler {
+                    let mime_types: Vec<String> = desktop_entry
+                        .main_group()
+                        .and_then(|group| group.get("MimeType"))
+                        .unwrap_or_default()
+                        .split(';')
+                        .filter(|mime_type| !mime_type.is_empty())
+                        .map(str::to_string)
+                        .collect();
+
+                    set_default_handler(
+                        config,
+                        &format!("{}.desktop", self.executable),
+                        &mime_types,
+                    )
+                    .await?;
+                }
             }
         }
 
-        // Clean up
-        fs::remove_dir_all(temp_dir).await?;
+        integration.mime_packages =
+            install_mime_packages(config, squashfs, &self.executable).await?;
+        refresh_desktop_database(config);
+
+        Ok(integration)
+    }
+    /// Also true for entries integrated before integration was recorded in the index.
+    pub fn is_integrated(&self, config: &Config) -> bool {
+        self.integration.is_some()
+            || self.is_own_desktop_file(
+                config,
+                &config
+                    .applications_dir()
+                    .join(format!("{}.desktop", self.executable)),
+            )
+    }
+    /// Whether a desktop file runs something of the app, so it's one zap-rs wrote
+    /// and not another with the same name.
+    fn is_own_desktop_file(&self, config: &Config, path: &Path) -> bool {
+        let Ok(content) = std::fs::read_to_string(path) else {
+            return false;
+        };
+
+        self.link_targets(config)
+            .iter()
+            .filter_map(|target| target.to_str())
+            .any(|target| content.contains(target))
+    }
+    /// Refuses to overwrite a desktop file that isn't the app's own, unless forced.
+    pub async fn check_desktop_files(&self, config: &Config, force: bool) -> Result<()> {
+        let desktop_file_paths = [
+            config
+                .desktops_dir()
+                .join(format!("{}.desktop", self.executable)),
+            config
+                .applications_dir()
+                .join(format!("{}.desktop", self.executable)),
+        ];
+
+        for path in desktop_file_paths {
+            let recorded = self
+                .integration
+                .as_ref()
+                .is_some_and(|integration| integration.desktop_files.contains(&path));
+
+            if force
+                || fs::symlink_metadata(&path).await.is_err()
+                || recorded
+                || self.is_own_desktop_file(config, &path)
+            {
+                continue;
+            }
+
+            return Err(Error::FileCollision { path, owner: None });
+        }
+
+        Ok(())
+    }
+    pub async fn unintegrate_desktop(&self, config: &Config) -> Result<()> {
+        if let Some(integration) = &self.integration {
+            for path in integration.desktop_files.iter().chain(&integration.icons) {
+                if fs::try_exists(path).await? {
+                    fs::remove_file(path).await?;
+                }
+            }
+            remove_mime_packages(config, &integration.mime_packages).await?;
+        }
+
+        // Entries from before integration was recorded only have the naming scheme to go by,
+        // so a desktop file is only removed when it runs the app
+        let legacy_desktop_files = [
+            config
+                .desktops_dir()
+                .join(format!("{}.desktop", self.executable)),
+            config
+                .applications_dir()
+                .join(format!("{}.desktop", self.executable)),
+        ];
+        let mut had_legacy_files = false;
+        for path in legacy_desktop_files {
+            if self.is_own_desktop_file(config, &path) {
+                fs::remove_file(path).await?;
+                had_legacy_files = true;
+            }
+        }
+
+        let legacy_icon = config
+            .legacy_icons_dir()
+            .join(format!("{}.png", self.executable));
+        if had_legacy_files && fs::try_exists(&legacy_icon).await? {
+            fs::remove_file(legacy_icon).await?;
+        }
+
+        remove_icons(config, &self.executable).await?;
+        remove_default_handler(config, &format!("{}.desktop", self.executable)).await?;
+        refresh_desktop_database(config);
 
         Ok(())
     }
@@ -1,13 +1,34 @@
 use clap::{Args, Parser, Subcommand};
+use std::path::PathBuf;
+
+use crate::{RunMode, SandboxBackend, Scope};
 
 /// A command line interface to install AppImages
 #[derive(Debug, Parser)]
 #[command(version, about, long_about = None)]
 pub struct Cli {
+    /// Manage the AppImages installed for all users
+    #[arg(long, global = true)]
+    pub system: bool,
+
+    /// Wait for another running zap-rs instead of giving up
+    #[arg(long, global = true)]
+    pub wait: bool,
+
     #[command(subcommand)]
     pub command: Command,
 }
 
+impl Cli {
+    pub fn scope(&self) -> Scope {
+        if self.system {
+            Scope::System
+        } else {
+            Scope::User
+        }
+    }
+}
+
 #[derive(Debug, Subcommand)]
 pub enum Command {
     /// Installs an AppImage (alias: i)
@@ -22,18 +43,97 @@ pub enum Command {
     #[command(name = "remove", alias = "rm")]
     Remove(RemoveArgs),
 
+    /// Adds an installed AppImage to the application menu
+    #[command(name = "integrate")]
+    Integrate(IntegrateArgs),
+
+    /// Removes an AppImage from the application menu, keeping the app installed
+    #[command(name = "unintegrate")]
+    Unintegrate(UnintegrateArgs),
+
+    /// Changes how an installed AppImage is run, e.g. without FUSE
+    #[command(name = "mode")]
+    Mode(ModeArgs),
+
+    /// Manages environment variables an AppImage is started with
+    #[command(name = "env", subcommand)]
+    Env(EnvCommand),
+
+    /// Sets the arguments an AppImage is started with, or clears them with --clear
+    #[command(name = "args")]
+    Args(ArgsArgs),
+
+    /// Manages the other names an AppImage can be started by
+    #[command(name = "alias", subcommand)]
+    Alias(AliasCommand),
+
+    /// Gives an AppImage its own home or config directory
+    #[command(name = "portable", subcommand)]
+    Portable(PortableCommand),
+
+    /// Shows or edits the sandbox an AppImage runs in
+    #[command(name = "sandbox")]
+    Sandbox(SandboxArgs),
+
+    /// Tells which installed AppImage a file belongs to
+    #[command(name = "owns")]
+    Owns(OwnsArgs),
+
     /// List the installed AppImages (alias: ls)
     #[command(name = "list", alias = "ls")]
     List,
+
+    /// Show what was installed, updated and removed when
+    #[command(name = "history")]
+    History,
+
+    /// Shows details about an installed AppImage
+    #[command(name = "info")]
+    Info(InfoArgs),
+
+    /// Looks inside an AppImage file or URL without running or installing it
+    #[command(name = "inspect")]
+    Inspect(InspectArgs),
+
+    /// Searches the recipe registries and the AppImage catalog (alias: s)
+    #[command(name = "search", alias = "s")]
+    Search(SearchArgs),
+
+    /// Manages recipe registries
+    #[command(name = "registry", subcommand)]
+    Registry(RegistryCommand),
+
+    /// Maintains the index of installed AppImages
+    #[command(name = "index", subcommand)]
+    Index(IndexCommand),
+}
+
+impl Command {
+    /// Whether the command changes anything in the store.
+    pub fn modifies(&self) -> bool {
+        !matches!(
+            self,
+            Command::List
+                | Command::History
+                | Command::Info(_)
+                | Command::Inspect(_)
+                | Command::Search(_)
+                | Command::Owns(_)
+                | Command::Portable(PortableCommand::Backup(_))
+                | Command::Registry(RegistryCommand::List)
+                | Command::Index(IndexCommand::Migrate(IndexMigrateArgs { dry_run: true }))
+        )
+    }
 }
 
 #[derive(Debug, Args)]
 pub struct InstallArgs {
     pub appname: String,
 
-    /// Provide a repository slug, or a direct URL to an appimage.
+    /// Provide a repository slug, a direct URL, or a local path (or file:// URL) to an appimage.
+    /// Looks the app up in the recipe registries and the AppImage catalog when omitted.
     #[arg(long)]
-    pub from: String,
+    pub from: Option<String>,
 
     /// Name of the executable
     #[arg(long)]
@@ -42,6 +142,22 @@ pub struct InstallArgs {
     /// Use --from as repository slug to fetch from GitHub
     #[arg(long, default_value_t = false)]
     pub github: bool,
+
+    /// Make the app the default handler for its MIME types and URL schemes when integrating
+    #[arg(long, default_value_t = false)]
+    pub default_handler: bool,
+
+    /// Another name to start the app by, can be given several times
+    #[arg(long = "alias", value_name = "NAME")]
+    pub aliases: Vec<String>,
+
+    /// Replace a file in the way of the command or desktop entry even if zap-rs didn't create it
+    #[arg(long, default_value_t = false)]
+    pub force: bool,
+
+    /// How to run the app, suggests extracting it when FUSE isn't available
+    #[arg(long, value_enum)]
+    pub mode: Option<RunMode>,
 }
 
 #[derive(Debug, Args)]
@@ -52,4 +168,301 @@ pub struct UpdateArgs {
 #[derive(Debug, Args)]
 pub struct RemoveArgs {
     pub appname: String,
+
+    /// Also delete the app's portable home and config directories
+    #[arg(long, default_value_t = false)]
+    pub purge: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct IntegrateArgs {
+    #[arg(required_unless_present = "all")]
+    pub appname: Option<String>,
+
+    /// Integrate every installed AppImage
+    #[arg(long, conflicts_with = "appname")]
+    pub all: bool,
+
+    /// Make the app the default handler for its MIME types and URL schemes
+    #[arg(long, default_value_t = false)]
+    pub default_handler: bool,
+
+    /// Replace a desktop file in the way even if zap-rs didn't create it
+    #[arg(long, default_value_t = false)]
+    pub force: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct UnintegrateArgs {
+    #[arg(required_unless_present = "all")]
+    pub appname: Option<String>,
+
+    /// Unintegrate every installed AppImage
+    #[arg(long, conflicts_with = "appname")]
+    pub all: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct ModeArgs {
+    pub appname: String,
+
+    #[arg(value_enum)]
+    pub mode: RunMode,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum EnvCommand {
+    /// Sets environment variables, given as KEY=VALUE
+    Set(EnvSetArgs),
+
+    /// Removes environment variables
+    Unset(EnvUnsetArgs),
+}
+
+#[derive(Debug, Args)]
+pub struct EnvSetArgs {
+    pub appname: String,
+
+    #[arg(required = true, value_name = "KEY=VALUE")]
+    pub vars: Vec<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct EnvUnsetArgs {
+    pub appname: String,
+
+    #[arg(required = true, value_name = "KEY")]
+    pub keys: Vec<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct ArgsArgs {
+    /// Remove the arguments instead of setting them
+    #[arg(long, default_value_t = false)]
+    pub clear: bool,
+
+    pub appname: String,
+
+    /// Passed ahead of any arguments given when starting the app
+    #[arg(
+        required_unless_present = "clear",
+        conflicts_with = "clear",
+        trailing_var_arg = true,
+        allow_hyphen_values = true
+    )]
+    pub args: Vec<String>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum AliasCommand {
+    /// Adds a command starting the app under another name
+    Add(AliasAddArgs),
+
+    /// Removes an alias (alias: rm)
+    #[command(alias = "rm")]
+    Remove(AliasRemoveArgs),
+}
+
+#[derive(Debug, Args)]
+pub struct AliasAddArgs {
+    pub appname: String,
+
+    pub alias: String,
+
+    /// Replace a file in the way of the alias even if zap-rs didn't create it
+    #[arg(long, default_value_t = false)]
+    pub force: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct AliasRemoveArgs {
+    pub appname: String,
+
+    pub alias: String,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum PortableCommand {
+    /// Starts using portable directories, both unless --home or --config is given
+    Enable(PortableArgs),
+
+    /// Stops using portable directories, keeping what's in them
+    Disable(PortableArgs),
+
+    /// Saves the portable directories to a tar archive
+    Backup(PortableBackupArgs),
+
+    /// Replaces the portable directories with the ones in a backup
+    Restore(PortableBackupArgs),
+}
+
+#[derive(Debug, Args)]
+pub struct PortableBackupArgs {
+    pub appname: String,
+
+    /// The archive, gzip compressed when backing up
+    pub archive: PathBuf,
+}
+
+#[derive(Debug, Args)]
+pub struct PortableArgs {
+    pub appname: String,
+
+    /// Only the home directory
+    #[arg(long, default_value_t = false)]
+    pub home: bool,
+
+    /// Only the config directory ($XDG_CONFIG_HOME)
+    #[arg(long, default_value_t = false)]
+    pub config: bool,
+}
+
+impl PortableArgs {
+    /// Which of the home and config directories are meant, neither flag means both.
+    pub fn dirs(&self) -> (bool, bool) {
+        let both = !self.home && !self.config;
+        (self.home || both, self.config || both)
+    }
+}
+
+#[derive(Debug, Args)]
+pub struct SandboxArgs {
+    pub appname: String,
+
+    /// Sandbox with bubblewrap (bwrap) or firejail, defaults to whichever is installed
+    #[arg(long, value_enum)]
+    pub backend: Option<SandboxBackend>,
+
+    /// Allow network access
+    #[arg(long, overrides_with = "no_network")]
+    pub network: bool,
+
+    /// Cut off network access
+    #[arg(long)]
+    pub no_network: bool,
+
+    /// Allow read-write access to the home directory
+    #[arg(long, overrides_with = "no_home")]
+    pub home: bool,
+
+    /// Hide the home directory
+    #[arg(long)]
+    pub no_home: bool,
+
+    /// Allow read-write access to a path, can be given several times
+    #[arg(long, value_name = "PATH")]
+    pub allow: Vec<PathBuf>,
+
+    /// Allow read access to a path, can be given several times
+    #[arg(long, value_name = "PATH")]
+    pub allow_read: Vec<PathBuf>,
+
+    /// Take back access to a path
+    #[arg(long, value_name = "PATH")]
+    pub revoke: Vec<PathBuf>,
+
+    /// Let a device in: dri, snd, video or a /dev path
+    #[arg(long, value_name = "DEVICE")]
+    pub device: Vec<String>,
+
+    /// Take a device back out
+    #[arg(long, value_name = "DEVICE")]
+    pub no_device: Vec<String>,
+
+    /// Stop sandboxing the app
+    #[arg(long, conflicts_with_all = ["backend", "network", "no_network", "home", "no_home", "allow", "allow_read", "revoke", "device", "no_device"])]
+    pub disable: bool,
+}
+
+impl SandboxArgs {
+    pub fn has_changes(&self) -> bool {
+        self.backend.is_some()
+            || self.network
+            || self.no_network
+            || self.home
+            || self.no_home
+            || !self.allow.is_empty()
+            || !self.allow_read.is_empty()
+            || !self.revoke.is_empty()
+            || !self.device.is_empty()
+            || !self.no_device.is_empty()
+    }
+}
+
+#[derive(Debug, Args)]
+pub struct OwnsArgs {
+    pub path: PathBuf,
+}
+
+#[derive(Debug, Args)]
+pub struct InfoArgs {
+    pub appname: String,
+
+    /// Print the details as JSON
+    #[arg(long, default_value_t = false)]
+    pub json: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct InspectArgs {
+    /// Path or URL of the AppImage
+    pub target: String,
+
+    /// Print the details as JSON
+    #[arg(long, default_value_t = false)]
+    pub json: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct SearchArgs {
+    pub term: String,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum RegistryCommand {
+    /// Adds a registry from a local directory, an HTTP(S) JSON index or a git repository
+    Add(RegistryAddArgs),
+
+    /// Removes a registry and its cache (alias: rm)
+    #[command(alias = "rm")]
+    Remove(RegistryRemoveArgs),
+
+    /// Lists the configured registries (alias: ls)
+    #[command(alias = "ls")]
+    List,
+
+    /// Fetches the latest recipes from every registry and the AppImage catalog
+    Refresh,
+}
+
+#[derive(Debug, Args)]
+pub struct RegistryAddArgs {
+    pub name: String,
+
+    pub url: String,
+
+    /// Bearer token used to fetch a private HTTP index
+    #[arg(long)]
+    pub token: Option<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct RegistryRemoveArgs {
+    pub name: String,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum IndexCommand {
+    /// Rewrites index entries from older versions of zap-rs, keeping backups
+    Migrate(IndexMigrateArgs),
+
+    /// Copies the JSON index into the database, when the index is kept in SQLite
+    Import,
+}
+
+#[derive(Debug, Args)]
+pub struct IndexMigrateArgs {
+    /// Only show what would be migrated
+    #[arg(long)]
+    pub dry_run: bool,
 }
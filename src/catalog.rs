@@ -0,0 +1,171 @@
+use fuzzy_matcher::{FuzzyMatcher, skim::SkimMatcherV2};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tokio::fs;
+
+use crate::{Config, Error, Recipe, Result, parse_github_url, write_atomically};
+
+pub const CATALOG_FEED_URL: &str = "https://appimage.github.io/feed.json";
+
+#[derive(Debug, Deserialize)]
+struct Feed {
+    #[serde(default)]
+    items: Vec<CatalogEntry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CatalogEntry {
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub categories: Option<Vec<String>>,
+    #[serde(default)]
+    pub license: Option<String>,
+    #[serde(default)]
+    pub links: Option<Vec<CatalogLink>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CatalogLink {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub url: String,
+}
+
+impl CatalogEntry {
+    /// The `owner/repo` slug from the GitHub link, or a GitHub download link as fallback.
+    pub fn github_slug(&self) -> Option<String> {
+        let links = self.links.as_ref()?;
+
+        let github_link = links
+            .iter()
+            .find(|link| link.kind.eq_ignore_ascii_case("github"))
+            .and_then(|link| {
+                if link.url.contains("github.com") {
+                    parse_github_url(&link.url).map(|github_url| github_url.slug)
+                } else {
+                    let (owner, repo) = link.url.trim_matches('/').split_once('/')?;
+                    (!repo.contains('/')).then(|| format!("{owner}/{repo}"))
+                }
+            });
+
+        github_link.or_else(|| {
+            links
+                .iter()
+                .find_map(|link| parse_github_url(&link.url).map(|github_url| github_url.slug))
+        })
+    }
+    pub fn to_recipe(&self) -> Option<Recipe> {
+        Some(Recipe {
+            name: self.name.to_string(),
+            description: self.description.clone().unwrap_or_default(),
+            source: self.github_slug()?,
+            github: true,
+            asset_pattern: None,
+            executable: None,
+            checksum: Default::default(),
+            registry: "catalog".to_string(),
+        })
+    }
+}
+
+pub fn parse_feed(content: &str) -> Result<Vec<CatalogEntry>> {
+    let feed: Feed = serde_json::from_str(content)?;
+
+    Ok(feed.items)
+}
+
+/// Fuzzy matches entries by name, categories and description, best matches first.
+pub fn search_entries<'a>(entries: &'a [CatalogEntry], term: &str) -> Vec<&'a CatalogEntry> {
+    let matcher = SkimMatcherV2::default().ignore_case();
+
+    let mut scored: Vec<(i64, &CatalogEntry)> = entries
+        .iter()
+        .filter_map(|entry| {
+            // Name matches weigh more than anything found in the rest of the entry
+            let name_score = matcher.fuzzy_match(&entry.name, term).map(|s| s * 2);
+            let category_score = entry
+                .categories
+                .iter()
+                .flatten()
+                .filter_map(|category| matcher.fuzzy_match(category, term))
+                .max();
+            let description_score = entry
+                .description
+                .as_ref()
+                .filter(|description| description.to_lowercase().contains(&term.to_lowercase()))
+                .and_then(|description| matcher.fuzzy_match(description, term));
+
+            [name_score, category_score, description_score]
+                .into_iter()
+                .flatten()
+                .max()
+                .map(|score| (score, entry))
+        })
+        .collect();
+
+    scored.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.name.cmp(&b.1.name)));
+
+    scored.into_iter().map(|(_, entry)| entry).collect()
+}
+
+#[derive(Debug)]
+pub struct Catalog {
+    config: Arc<Config>,
+    client: reqwest::Client,
+}
+
+impl Catalog {
+    pub fn new(config: Arc<Config>, client: reqwest::Client) -> Self {
+        Self { config, client }
+    }
+    pub async fn refresh(&self) -> Result<()> {
+        let resp = self
+            .client
+            .get(CATALOG_FEED_URL)
+            .send()
+            .await
+            .and_then(|resp| resp.error_for_status())
+            .map_err(|source| Error::Download {
+                url: CATALOG_FEED_URL.to_string(),
+                source,
+            })?;
+        let body = resp.text().await?;
+
+        // Make sure we never cache something we can't read back
+        parse_feed(&body)?;
+
+        let catalog_file = self.config.catalog_file();
+        if let Some(parent) = catalog_file.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+        write_atomically(&catalog_file, body.as_bytes()).await?;
+
+        Ok(())
+    }
+    pub async fn entries(&self) -> Result<Vec<CatalogEntry>> {
+        let catalog_file = self.config.catalog_file();
+
+        if !fs::try_exists(&catalog_file).await? {
+            self.refresh().await?;
+        }
+
+        parse_feed(&fs::read_to_string(catalog_file).await?)
+    }
+    pub async fn get(&self, name: &str) -> Result<Option<CatalogEntry>> {
+        Ok(self
+            .entries()
+            .await?
+            .into_iter()
+            .find(|entry| entry.name.eq_ignore_ascii_case(name)))
+    }
+    pub async fn search(&self, term: &str) -> Result<Vec<CatalogEntry>> {
+        let entries = self.entries().await?;
+
+        Ok(search_entries(&entries, term)
+            .into_iter()
+            .cloned()
+            .collect())
+    }
+}
@@ -0,0 +1,268 @@
+use serde::{Deserialize, Serialize};
+use std::{fmt, path::PathBuf, time::Duration};
+
+use crate::{BaseDirs, Error, Result};
+
+/// Whether to add freshly installed apps to the application menu.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IntegrateMode {
+    #[default]
+    Ask,
+    Always,
+    Never,
+}
+
+/// Whether apps are installed for the current user or for everyone.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Scope {
+    #[default]
+    User,
+    System,
+}
+
+impl fmt::Display for Scope {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Scope::User => write!(fmt, "user"),
+            Scope::System => write!(fmt, "system"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PathsConfig {
+    /// Where AppImages, the index and everything else zap-rs keeps live
+    pub store: Option<PathBuf>,
+    pub bin: Option<PathBuf>,
+    pub applications: Option<PathBuf>,
+    /// Icon themes, icons go into the `hicolor` theme in there
+    pub icons: Option<PathBuf>,
+    pub mime: Option<PathBuf>,
+    pub mimeapps: Option<PathBuf>,
+    /// Downloads in progress, registry and catalog caches
+    pub cache: Option<PathBuf>,
+    /// The install history
+    pub state: Option<PathBuf>,
+}
+
+/// How the index of installed AppImages is stored.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IndexBackend {
+    /// One JSON file per app
+    #[default]
+    Json,
+    /// A single SQLite database, imported from the JSON files the first time
+    Sqlite,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct IndexConfig {
+    pub backend: IndexBackend,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct IntegrationConfig {
+    pub integrate: IntegrateMode,
+    pub default_handler: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NetworkConfig {
+    /// How many registries are refreshed at once
+    pub concurrency: usize,
+    /// Seconds to wait for a connection or for data before giving up
+    pub timeout: u64,
+    /// Used for downloads, registries and the catalog, the GitHub API client doesn't support one
+    pub proxy: Option<String>,
+    /// Raises the GitHub API rate limit and gives access to private repositories
+    pub github_token: Option<String>,
+}
+
+impl Default for NetworkConfig {
+    fn default() -> Self {
+        Self {
+            concurrency: 4,
+            timeout: 30,
+            proxy: None,
+            github_token: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SourcesConfig {
+    /// Asset pattern for GitHub sources that don't pick an asset themselves
+    pub asset_pattern: Option<String>,
+    /// Install the latest GitHub release instead of asking for one
+    pub latest: bool,
+}
+
+/// Contents of `config.toml`, every setting is optional.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub paths: PathsConfig,
+    /// Paths for `--system`, under `/opt/zap-rs`, `/usr/local/bin` and `/usr/share` by default
+    pub system: PathsConfig,
+    pub index: IndexConfig,
+    pub integration: IntegrationConfig,
+    pub network: NetworkConfig,
+    pub sources: SourcesConfig,
+    #[serde(skip)]
+    pub dirs: BaseDirs,
+    #[serde(skip)]
+    pub scope: Scope,
+}
+
+impl Config {
+    /// Reads the config file, falling back to the defaults when there's none.
+    pub async fn load() -> Result<Self> {
+        let dirs = BaseDirs::from_env()?;
+        let path = dirs.config_file();
+
+        let content = match tokio::fs::read_to_string(&path).await {
+            Ok(content) => content,
+            // Only a file asked for with ZAP_RS_CONFIG has to exist
+            Err(e)
+                if e.kind() == std::io::ErrorKind::NotFound
+                    && std::env::var_os("ZAP_RS_CONFIG").is_none() =>
+            {
+                String::new()
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return Err(Error::FileNotFound(path));
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        Self::parse(&content, dirs)
+    }
+    pub fn parse(content: &str, dirs: BaseDirs) -> Result<Self> {
+        let mut config: Config = toml::from_str(content)?;
+        config.dirs = dirs;
+
+        Ok(config)
+    }
+    /// Switches to the paths of the scope, the system one has a store of its own.
+    pub fn with_scope(mut self, scope: Scope) -> Self {
+        if scope == Scope::System {
+            self.dirs = BaseDirs::system(self.dirs.home);
+            self.paths = self.system.clone();
+        }
+        self.scope = scope;
+
+        self
+    }
+    fn path(&self, configured: &Option<PathBuf>, default: PathBuf) -> PathBuf {
+        match configured {
+            Some(path) => match path.strip_prefix("~") {
+                Ok(rest) => self.dirs.home.join(rest),
+                Err(_) => path.clone(),
+            },
+            None => default,
+        }
+    }
+    pub fn http_client(&self) -> Result<reqwest::Client> {
+        let timeout = Duration::from_secs(self.network.timeout);
+        let mut builder = reqwest::Client::builder()
+            .connect_timeout(timeout)
+            .read_timeout(timeout);
+
+        if let Some(proxy) = &self.network.proxy {
+            builder = builder.proxy(reqwest::Proxy::all(proxy)?);
+        }
+
+        Ok(builder.build()?)
+    }
+    pub fn store_dir(&self) -> PathBuf {
+        let default = match self.scope {
+            Scope::User => self.dirs.data.join("zap-rs"),
+            Scope::System => PathBuf::from("/opt/zap-rs"),
+        };
+
+        self.path(&self.paths.store, default)
+    }
+    pub fn bin_dir(&self) -> PathBuf {
+        self.path(&self.paths.bin, self.dirs.bin.clone())
+    }
+    pub fn applications_dir(&self) -> PathBuf {
+        self.path(
+            &self.paths.applications,
+            self.dirs.data.join("applications"),
+        )
+    }
+    pub fn hicolor_dir(&self) -> PathBuf {
+        self.path(&self.paths.icons, self.dirs.data.join("icons"))
+            .join("hicolor")
+    }
+    pub fn mime_dir(&self) -> PathBuf {
+        self.path(&self.paths.mime, self.dirs.data.join("mime"))
+    }
+    pub fn mimeapps_list(&self) -> PathBuf {
+        self.path(&self.paths.mimeapps, self.dirs.config.join("mimeapps.list"))
+    }
+    /// Nothing in here is needed to run the installed apps, it can be deleted at any time.
+    pub fn cache_dir(&self) -> PathBuf {
+        self.path(&self.paths.cache, self.dirs.cache.join("zap-rs"))
+    }
+    pub fn state_dir(&self) -> PathBuf {
+        self.path(&self.paths.state, self.dirs.state.join("zap-rs"))
+    }
+    pub fn index_db_file(&self) -> PathBuf {
+        self.store_dir().join("index.db")
+    }
+    pub fn index_backups_dir(&self) -> PathBuf {
+        self.store_dir().join("backups/index")
+    }
+    pub fn lock_file(&self) -> PathBuf {
+        self.store_dir().join("zap-rs.lock")
+    }
+    pub fn downloads_dir(&self) -> PathBuf {
+        self.cache_dir().join("downloads")
+    }
+    pub fn history_file(&self) -> PathBuf {
+        self.state_dir().join("history.log")
+    }
+    pub fn index_dir(&self) -> PathBuf {
+        self.store_dir().join("index")
+    }
+    pub fn appimages_dir(&self) -> PathBuf {
+        self.store_dir().join("appimages")
+    }
+    pub fn appdirs_dir(&self) -> PathBuf {
+        self.store_dir().join("appdirs")
+    }
+    pub fn launchers_dir(&self) -> PathBuf {
+        self.store_dir().join("launchers")
+    }
+    pub fn portable_dir(&self) -> PathBuf {
+        self.store_dir().join("portable")
+    }
+    pub fn desktops_dir(&self) -> PathBuf {
+        self.store_dir().join("desktops")
+    }
+    /// Where icons went before they were installed into the icon theme.
+    pub fn legacy_icons_dir(&self) -> PathBuf {
+        self.store_dir().join("icons")
+    }
+    pub fn recipes_dir(&self) -> PathBuf {
+        self.store_dir().join("recipes")
+    }
+    pub fn registry_cache_dir(&self) -> PathBuf {
+        self.cache_dir().join("registry")
+    }
+    pub fn registries_file(&self) -> PathBuf {
+        self.store_dir().join("registries.json")
+    }
+    pub fn catalog_file(&self) -> PathBuf {
+        self.cache_dir().join("catalog/feed.json")
+    }
+}
@@ -0,0 +1,538 @@
+use std::fmt;
+
+use crate::{Error, Result};
+
+pub const DESKTOP_ENTRY_GROUP: &str = "Desktop Entry";
+
+#[derive(Debug, Clone)]
+enum Line {
+    /// Comments and blank lines, kept verbatim
+    Other(String),
+    Entry {
+        key: String,
+        locale: Option<String>,
+        /// Still escaped, as found in the file
+        value: String,
+        /// The original line, dropped once the entry is modified
+        raw: Option<String>,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub struct DesktopGroup {
+    pub name: String,
+    header: String,
+    lines: Vec<Line>,
+}
+
+/// A Desktop Entry file per the freedesktop.org specification, preserving everything it
+/// doesn't touch so it can be written back losslessly.
+#[derive(Debug, Clone)]
+pub struct DesktopEntry {
+    preamble: Vec<String>,
+    groups: Vec<DesktopGroup>,
+    /// `\r\n` for files written that way, `\n` otherwise
+    line_ending: &'static str,
+    trailing_newline: bool,
+}
+
+pub fn unescape_value(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    let mut chars = value.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('s') => out.push(' '),
+            Some('n') => out.push('\n'),
+            Some('t') => out.push('\t'),
+            Some('r') => out.push('\r'),
+            Some('\\') => out.push('\\'),
+            Some(other) => {
+                // Leave unknown escapes such as `\;` for list values alone
+                out.push('\\');
+                out.push(other);
+            }
+            None => out.push('\\'),
+        }
+    }
+
+    out
+}
+
+pub fn escape_value(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+
+    for (i, c) in value.chars().enumerate() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            '\r' => out.push_str("\\r"),
+            ' ' if i == 0 => out.push_str("\\s"),
+            _ => out.push(c),
+        }
+    }
+
+    out
+}
+
+/// Splits an (unescaped) Exec value into its arguments, honouring the quoting rules.
+/// Field codes like `%U` are kept as their own arguments.
+pub fn parse_exec(exec: &str) -> Result<Vec<String>> {
+    let mut args = vec![];
+    let mut current = String::new();
+    let mut in_arg = false;
+    let mut chars = exec.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => {
+                in_arg = true;
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(escaped @ ('"' | '`' | '$' | '\\')) => current.push(escaped),
+                            Some(other) => {
+                                current.push('\\');
+                                current.push(other);
+                            }
+                            None => {
+                                return Err(Error::InvalidDesktopEntry(format!(
+                                    "unterminated quote in Exec '{exec}'"
+                                )));
+                            }
+                        },
+                        Some(other) => current.push(other),
+                        None => {
+                            return Err(Error::InvalidDesktopEntry(format!(
+                                "unterminated quote in Exec '{exec}'"
+                            )));
+                        }
+                    }
+                }
+            }
+            ' ' | '\t' | '\n' => {
+                if in_arg {
+                    args.push(std::mem::take(&mut current));
+                    in_arg = false;
+                }
+            }
+            _ => {
+                in_arg = true;
+                current.push(c);
+            }
+        }
+    }
+
+    if in_arg {
+        args.push(current);
+    }
+
+    Ok(args)
+}
+
+fn is_field_code(arg: &str) -> bool {
+    arg.len() == 2 && arg.starts_with('%')
+}
+
+/// Joins arguments into an (unescaped) Exec value, quoting where the spec requires it.
+pub fn build_exec(args: &[String]) -> String {
+    args.iter()
+        .map(|arg| {
+            let reserved = arg.is_empty()
+                || arg.chars().any(|c| {
+                    matches!(
+                        c,
+                        ' ' | '\t'
+                            | '\n'
+                            | '"'
+                            | '\''
+                            | '\\'
+                            | '>'
+                            | '<'
+                            | '~'
+                            | '|'
+                            | '&'
+                            | ';'
+                            | '$'
+                            | '*'
+                            | '?'
+                            | '#'
+                            | '('
+                            | ')'
+                            | '`'
+                    )
+                });
+
+            if !reserved || is_field_code(arg) {
+                return arg.to_string();
+            }
+
+            let mut quoted = String::from('"');
+            for c in arg.chars() {
+                if matches!(c, '"' | '`' | '$' | '\\') {
+                    quoted.push('\\');
+                }
+                quoted.push(c);
+            }
+            quoted.push('"');
+            quoted
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Escapes `%` so a path can be used as a literal Exec argument.
+pub fn exec_literal(arg: &str) -> String {
+    arg.replace('%', "%%")
+}
+
+fn split_key(key: &str) -> (String, Option<String>) {
+    match key.split_once('[') {
+        Some((key, locale)) if locale.ends_with(']') => (
+            key.to_string(),
+            Some(locale.trim_end_matches(']').to_string()),
+        ),
+        _ => (key.to_string(), None),
+    }
+}
+
+impl DesktopGroup {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            header: format!("[{name}]"),
+            lines: vec![],
+        }
+    }
+    pub fn is_action(&self) -> bool {
+        self.name.starts_with("Desktop Action ")
+    }
+    fn raw(&self, key: &str, locale: Option<&str>) -> Option<&str> {
+        self.lines.iter().find_map(|line| match line {
+            Line::Entry {
+                key: k,
+                locale: l,
+                value,
+                ..
+            } if k == key && l.as_deref() == locale => Some(value.as_str()),
+            _ => None,
+        })
+    }
+    pub fn get(&self, key: &str) -> Option<String> {
+        self.raw(key, None).map(unescape_value)
+    }
+    /// Looks a key up following the spec's locale matching order, e.g. `de_DE@euro` tries
+    /// `de_DE@euro`, `de_DE`, `de@euro`, `de`, then the unlocalized key.
+    pub fn get_localized(&self, key: &str, locale: &str) -> Option<String> {
+        let (rest, modifier) = match locale.split_once('@') {
+            Some((rest, modifier)) => (rest, Some(modifier)),
+            None => (locale, None),
+        };
+        let (lang, country) = match rest.split_once('_') {
+            Some((lang, country)) => (lang, Some(country)),
+            None => (rest, None),
+        };
+
+        let mut candidates = vec![];
+        if let (Some(country), Some(modifier)) = (country, modifier) {
+            candidates.push(format!("{lang}_{country}@{modifier}"));
+        }
+        if let Some(country) = country {
+            candidates.push(format!("{lang}_{country}"));
+        }
+        if let Some(modifier) = modifier {
+            candidates.push(format!("{lang}@{modifier}"));
+        }
+        candidates.push(lang.to_string());
+
+        candidates
+            .iter()
+            .find_map(|candidate| self.raw(key, Some(candidate)))
+            .or_else(|| self.raw(key, None))
+            .map(unescape_value)
+    }
+    /// Unlocalized keys with their unescaped values, in file order.
+    pub fn entries(&self) -> Vec<(String, String)> {
+        self.lines
+            .iter()
+            .filter_map(|line| match line {
+                Line::Entry {
+                    key,
+                    locale: None,
+                    value,
+                    ..
+                } => Some((key.to_string(), unescape_value(value))),
+                _ => None,
+            })
+            .collect()
+    }
+    pub fn contains(&self, key: &str) -> bool {
+        self.raw(key, None).is_some()
+    }
+    /// Sets the unlocalized value of a key, appending it if the group doesn't have it yet.
+    pub fn set(&mut self, key: &str, value: &str) {
+        self.set_raw(key, None, escape_value(value));
+    }
+    fn set_raw(&mut self, key: &str, locale: Option<&str>, escaped: String) {
+        for line in &mut self.lines {
+            if let Line::Entry {
+                key: k,
+                locale: l,
+                value,
+                raw,
+            } = line
+                && k == key
+                && l.as_deref() == locale
+            {
+                if *value != escaped {
+                    *value = escaped;
+                    *raw = None;
+                }
+                return;
+            }
+        }
+
+        // Keep trailing blank lines and comments after the new entry
+        let position = self
+            .lines
+            .iter()
+            .rposition(|line| matches!(line, Line::Entry { .. }))
+            .map_or(0, |i| i + 1);
+
+        self.lines.insert(
+            position,
+            Line::Entry {
+                key: key.to_string(),
+                locale: locale.map(str::to_string),
+                value: escaped,
+                raw: None,
+            },
+        );
+    }
+    /// Sets a key in every locale it is present in.
+    pub fn set_all_locales(&mut self, key: &str, value: &str) {
+        let escaped = escape_value(value);
+
+        for line in &mut self.lines {
+            if let Line::Entry {
+                key: k, value, raw, ..
+            } = line
+                && k == key
+                && *value != escaped
+            {
+                *value = escaped.to_string();
+                *raw = None;
+            }
+        }
+    }
+    pub fn remove(&mut self, key: &str) {
+        self.lines
+            .retain(|line| !matches!(line, Line::Entry { key: k, locale: None, .. } if k == key));
+    }
+    /// Replaces the program of the Exec line, keeping its arguments and field codes.
+    pub fn set_exec_program(&mut self, program: &str) -> Result<()> {
+        let Some(exec) = self.get("Exec") else {
+            return Ok(());
+        };
+
+        let mut args = parse_exec(&exec)?;
+        let program = exec_literal(program);
+
+        if args.is_empty() {
+            args.push(program);
+        } else {
+            args[0] = program;
+        }
+
+        self.set("Exec", &build_exec(&args));
+
+        Ok(())
+    }
+}
+
+impl DesktopEntry {
+    pub fn new() -> Self {
+        Self {
+            preamble: vec![],
+            groups: vec![DesktopGroup::new(DESKTOP_ENTRY_GROUP)],
+            line_ending: "\n",
+            trailing_newline: true,
+        }
+    }
+    pub fn parse(content: &str) -> Result<Self> {
+        let mut preamble = vec![];
+        let mut groups: Vec<DesktopGroup> = vec![];
+
+        for (number, line) in content.lines().enumerate() {
+            let trimmed = line.trim();
+
+            if trimmed.is_empty() || trimmed.starts_with('#') {
+                match groups.last_mut() {
+                    Some(group) => group.lines.push(Line::Other(line.to_string())),
+                    None => preamble.push(line.to_string()),
+                }
+                continue;
+            }
+
+            if trimmed.starts_with('[') && trimmed.ends_with(']') {
+                let name = &trimmed[1..trimmed.len() - 1];
+
+                if groups.iter().any(|group| group.name == name) {
+                    return Err(Error::InvalidDesktopEntry(format!(
+                        "duplicate group [{name}]"
+                    )));
+                }
+
+                groups.push(DesktopGroup {
+                    name: name.to_string(),
+                    header: line.to_string(),
+                    lines: vec![],
+                });
+                continue;
+            }
+
+            let (Some(group), Some((key, value))) = (groups.last_mut(), line.split_once('='))
+            else {
+                return Err(Error::InvalidDesktopEntry(format!(
+                    "unexpected line {}: '{line}'",
+                    number + 1
+                )));
+            };
+
+            let (key, locale) = split_key(key.trim());
+
+            group.lines.push(Line::Entry {
+                key,
+                locale,
+                value: value.trim_start().to_string(),
+                raw: Some(line.to_string()),
+            });
+        }
+
+        let first_line = content.split_inclusive('\n').next().unwrap_or_default();
+
+        Ok(Self {
+            preamble,
+            groups,
+            line_ending: if first_line.ends_with("\r\n") {
+                "\r\n"
+            } else {
+                "\n"
+            },
+            trailing_newline: content.is_empty() || content.ends_with('\n'),
+        })
+    }
+    pub fn groups(&self) -> impl Iterator<Item = &DesktopGroup> {
+        self.groups.iter()
+    }
+    pub fn groups_mut(&mut self) -> impl Iterator<Item = &mut DesktopGroup> {
+        self.groups.iter_mut()
+    }
+    pub fn group(&self, name: &str) -> Option<&DesktopGroup> {
+        self.groups.iter().find(|group| group.name == name)
+    }
+    pub fn group_mut(&mut self, name: &str) -> Option<&mut DesktopGroup> {
+        self.groups.iter_mut().find(|group| group.name == name)
+    }
+    /// Returns the group, creating it at the end if it doesn't exist.
+    pub fn group_or_insert(&mut self, name: &str) -> &mut DesktopGroup {
+        match self.groups.iter().position(|group| group.name == name) {
+            Some(i) => &mut self.groups[i],
+            None => {
+                self.groups.push(DesktopGroup::new(name));
+                self.groups.last_mut().unwrap()
+            }
+        }
+    }
+    pub fn main_group(&self) -> Option<&DesktopGroup> {
+        self.group(DESKTOP_ENTRY_GROUP)
+    }
+    pub fn validate(&self) -> Result<()> {
+        let invalid = |msg: &str| Err(Error::InvalidDesktopEntry(msg.to_string()));
+
+        let Some(main) = self.groups.first() else {
+            return invalid("no groups");
+        };
+        if main.name != DESKTOP_ENTRY_GROUP {
+            return invalid("the first group must be [Desktop Entry]");
+        }
+
+        let Some(kind) = main.get("Type") else {
+            return invalid("missing Type key");
+        };
+        if main.get("Name").is_none() {
+            return invalid("missing Name key");
+        }
+
+        if kind == "Application"
+            && main.get("DBusActivatable").as_deref() != Some("true")
+            && main.get("Exec").is_none()
+        {
+            return invalid("missing Exec key");
+        }
+
+        for group in &self.groups {
+            if let Some(exec) = group.get("Exec") {
+                parse_exec(&exec)?;
+            }
+        }
+
+        for action in main.get("Actions").unwrap_or_default().split(';') {
+            if !action.is_empty() && self.group(&format!("Desktop Action {action}")).is_none() {
+                return Err(Error::InvalidDesktopEntry(format!(
+                    "missing [Desktop Action {action}] group"
+                )));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for DesktopEntry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Display for DesktopEntry {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let mut lines: Vec<String> = self.preamble.clone();
+
+        for group in &self.groups {
+            lines.push(group.header.to_string());
+
+            for line in &group.lines {
+                lines.push(match line {
+                    Line::Other(line) => line.to_string(),
+                    Line::Entry { raw: Some(raw), .. } => raw.to_string(),
+                    Line::Entry {
+                        key,
+                        locale: Some(locale),
+                        value,
+                        raw: None,
+                    } => format!("{key}[{locale}]={value}"),
+                    Line::Entry {
+                        key,
+                        locale: None,
+                        value,
+                        raw: None,
+                    } => format!("{key}={value}"),
+                });
+            }
+        }
+
+        write!(fmt, "{}", lines.join(self.line_ending))?;
+        if self.trailing_newline && !lines.is_empty() {
+            write!(fmt, "{}", self.line_ending)?;
+        }
+
+        Ok(())
+    }
+}
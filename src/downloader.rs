@@ -1,15 +1,25 @@
 use futures_util::StreamExt;
-use std::path::PathBuf;
-use tokio::{fs, io::AsyncWriteExt};
+use sha2::{Digest, Sha256};
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use tokio::{
+    fs,
+    io::{AsyncReadExt, AsyncWriteExt},
+};
 
-use crate::{Error, Result, appimages_dir, make_progress_bar};
+use crate::{Config, Error, Result, make_progress_bar};
 
-#[derive(Debug, Default)]
-pub struct Downloader {}
+#[derive(Debug)]
+pub struct Downloader {
+    config: Arc<Config>,
+    client: reqwest::Client,
+}
 
 impl Downloader {
-    pub fn new() -> Self {
-        Self {}
+    pub fn new(config: Arc<Config>, client: reqwest::Client) -> Self {
+        Self { config, client }
     }
     pub fn prepare_path(&self, url: &str, executable: &str) -> Result<PathBuf> {
         // Try to extract filename from URL or use default
@@ -24,7 +34,7 @@ impl Downloader {
             None => format!("{executable}.AppImage"),
         };
 
-        Ok(appimages_dir()?.join(filename))
+        Ok(self.config.appimages_dir().join(filename))
     }
     pub fn validate_response(&self, resp: &reqwest::Response) -> Result<()> {
         if !resp.status().is_success() {
@@ -63,12 +73,115 @@ impl Downloader {
 
         Ok(())
     }
-    pub async fn download_with_progress(&self, url: &str, path: &PathBuf) -> Result<()> {
-        fs::create_dir_all(&appimages_dir()?).await?;
+    pub async fn validate_file(&self, path: &Path) -> Result<()> {
+        let metadata = fs::metadata(path).await.map_err(|e| {
+            if e.kind() == std::io::ErrorKind::NotFound {
+                Error::FileNotFound(path.to_path_buf())
+            } else {
+                Error::from(e)
+            }
+        })?;
+
+        if !metadata.is_file() || metadata.len() < 1024 {
+            return Err(Error::InvalidAppImage);
+        }
+
+        // ELF magic followed by the AppImage magic at offset 8
+        let mut header = [0u8; 11];
+        fs::File::open(path).await?.read_exact(&mut header).await?;
 
-        let temp_path = PathBuf::from(format!("{}.part", path.display()));
+        if &header[..4] != b"\x7fELF" || &header[8..10] != b"AI" {
+            return Err(Error::InvalidAppImage);
+        }
 
-        let resp = reqwest::get(&url.to_string())
+        Ok(())
+    }
+    pub async fn hash_file(&self, path: &Path) -> Result<String> {
+        let mut file = fs::File::open(path).await?;
+        let mut hasher = Sha256::new();
+        let mut buf = vec![0u8; 64 * 1024];
+
+        loop {
+            let n = file.read(&mut buf).await?;
+            if n == 0 {
+                break;
+            }
+            hasher.update(&buf[..n]);
+        }
+
+        Ok(hex::encode(hasher.finalize()))
+    }
+    async fn make_executable(&self, path: &Path) -> Result<()> {
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mut perms = fs::metadata(&path).await?.permissions();
+            perms.set_mode(0o755);
+            fs::set_permissions(&path, perms).await?;
+        }
+
+        Ok(())
+    }
+    /// Where a file is put together before it's moved to `path`.
+    async fn part_path(&self, path: &Path) -> Result<PathBuf> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+        fs::create_dir_all(self.config.downloads_dir()).await?;
+
+        let file_name = path.file_name().ok_or(Error::InvalidPath)?;
+        let mut part_name = file_name.to_os_string();
+        part_name.push(".part");
+
+        Ok(self.config.downloads_dir().join(part_name))
+    }
+    /// The cache may well be on another filesystem than the store, then the file is copied
+    /// next to `path` first so an interrupted copy never leaves a truncated file behind.
+    async fn move_into_place(&self, part_path: &Path, path: &Path) -> Result<()> {
+        match fs::rename(part_path, path).await {
+            Err(e) if e.kind() == std::io::ErrorKind::CrossesDevices => {
+                let file_name = path.file_name().ok_or(Error::InvalidPath)?;
+                let mut temp_name = std::ffi::OsString::from(".");
+                temp_name.push(file_name);
+                temp_name.push(".part");
+                let temp_path = path.with_file_name(temp_name);
+
+                if let Err(e) = fs::copy(part_path, &temp_path).await {
+                    let _ = fs::remove_file(&temp_path).await;
+                    return Err(e.into());
+                }
+                fs::rename(&temp_path, path).await?;
+                fs::remove_file(part_path).await?;
+            }
+            result => result?,
+        }
+
+        Ok(())
+    }
+    /// Moves a file fetched with `download_part` or `copy_part` to `path`.
+    pub async fn install_part(&self, part_path: &Path, path: &Path) -> Result<()> {
+        self.move_into_place(part_path, path).await?;
+        self.make_executable(path).await
+    }
+    pub async fn download_with_progress(&self, url: &str, path: &Path) -> Result<String> {
+        let (temp_path, sha256) = self.download_part(url, path).await?;
+        self.install_part(&temp_path, path).await?;
+
+        Ok(sha256)
+    }
+    /// Downloads the file meant for `path`, leaving it in the downloads directory for now.
+    pub async fn download_part(&self, url: &str, path: &Path) -> Result<(PathBuf, String)> {
+        let temp_path = self.part_path(path).await?;
+        let sha256 = self.download_to(url, &temp_path).await?;
+
+        Ok((temp_path, sha256))
+    }
+    /// Downloads straight to `temp_path`, for files that aren't meant for the store.
+    pub async fn download_to(&self, url: &str, temp_path: &Path) -> Result<String> {
+        let resp = self
+            .client
+            .get(url)
+            .send()
             .await
             .map_err(|source| Error::Download {
                 url: url.to_string(),
@@ -80,7 +193,8 @@ impl Downloader {
         let total_size = resp.content_length().unwrap_or(0);
 
         let bar = make_progress_bar(total_size)?;
-        let mut out = tokio::fs::File::create(&temp_path).await?;
+        let mut out = tokio::fs::File::create(temp_path).await?;
+        let mut hasher = Sha256::new();
 
         // Stream download with progress updates
         let mut stream = resp.bytes_stream();
@@ -97,22 +211,47 @@ impl Downloader {
             };
             let len = chunk.len() as u64;
             out.write_all(&chunk).await?;
+            hasher.update(&chunk);
             bar.inc(len);
         }
+        out.flush().await?;
 
         bar.finish_with_message("Download complete!");
 
-        fs::rename(temp_path, path).await?;
+        Ok(hex::encode(hasher.finalize()))
+    }
+    /// Copies a local file meant for `path`, leaving it in the downloads directory for now.
+    pub async fn copy_part(&self, source: &Path, path: &Path) -> Result<(PathBuf, String)> {
+        self.validate_file(source).await?;
 
-        // Make executable
-        #[cfg(unix)]
-        {
-            use std::os::unix::fs::PermissionsExt;
-            let mut perms = fs::metadata(&path).await?.permissions();
-            perms.set_mode(0o755);
-            fs::set_permissions(&path, perms).await?;
+        let temp_path = self.part_path(path).await?;
+
+        if fs::try_exists(&temp_path).await? {
+            fs::remove_file(&temp_path).await?;
         }
 
-        Ok(())
+        // Always a copy: a hardlink would share later edits and permission changes with the source
+        let total_size = fs::metadata(source).await?.len();
+
+        let bar = make_progress_bar(total_size)?;
+        let mut input = fs::File::open(source).await?;
+        let mut out = fs::File::create(&temp_path).await?;
+        let mut hasher = Sha256::new();
+        let mut buf = vec![0u8; 64 * 1024];
+
+        loop {
+            let n = input.read(&mut buf).await?;
+            if n == 0 {
+                break;
+            }
+            out.write_all(&buf[..n]).await?;
+            hasher.update(&buf[..n]);
+            bar.inc(n as u64);
+        }
+        out.flush().await?;
+
+        bar.finish_with_message("Copy complete!");
+
+        Ok((temp_path, hex::encode(hasher.finalize())))
     }
 }
@@ -0,0 +1,314 @@
+use std::{io::SeekFrom, path::Path};
+use tokio::{
+    fs,
+    io::{AsyncReadExt, AsyncSeekExt},
+};
+
+use crate::{Error, Result};
+
+#[derive(Debug, Clone)]
+pub struct ElfSection {
+    pub name: String,
+    pub offset: u64,
+    pub size: u64,
+}
+
+/// The parts of an ELF header we care about, read without loading the whole file.
+#[derive(Debug, Clone)]
+pub struct ElfInfo {
+    pub is_64: bool,
+    pub little_endian: bool,
+    pub machine: u16,
+    pub interpreter: bool,
+    pub sections: Vec<ElfSection>,
+    /// Where the ELF ends, which is where an AppImage's filesystem image starts
+    pub end: u64,
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    little_endian: bool,
+}
+
+impl Reader<'_> {
+    fn u16(&self, offset: usize) -> Result<u16> {
+        let bytes: [u8; 2] = self
+            .bytes
+            .get(offset..offset + 2)
+            .and_then(|b| b.try_into().ok())
+            .ok_or(Error::InvalidAppImage)?;
+        Ok(if self.little_endian {
+            u16::from_le_bytes(bytes)
+        } else {
+            u16::from_be_bytes(bytes)
+        })
+    }
+    fn u32(&self, offset: usize) -> Result<u32> {
+        let bytes: [u8; 4] = self
+            .bytes
+            .get(offset..offset + 4)
+            .and_then(|b| b.try_into().ok())
+            .ok_or(Error::InvalidAppImage)?;
+        Ok(if self.little_endian {
+            u32::from_le_bytes(bytes)
+        } else {
+            u32::from_be_bytes(bytes)
+        })
+    }
+    fn u64(&self, offset: usize) -> Result<u64> {
+        let bytes: [u8; 8] = self
+            .bytes
+            .get(offset..offset + 8)
+            .and_then(|b| b.try_into().ok())
+            .ok_or(Error::InvalidAppImage)?;
+        Ok(if self.little_endian {
+            u64::from_le_bytes(bytes)
+        } else {
+            u64::from_be_bytes(bytes)
+        })
+    }
+}
+
+async fn read_at(file: &mut fs::File, offset: u64, len: usize) -> Result<Vec<u8>> {
+    let mut buf = vec![0u8; len];
+    file.seek(SeekFrom::Start(offset)).await?;
+    file.read_exact(&mut buf).await?;
+    Ok(buf)
+}
+
+/// appimagetool reserves 1024 bytes for `.upd_info` and `.sha256_sig` and 8192 for `.sig_key`.
+const MAX_SECTION_SIZE: u64 = 64 * 1024;
+
+/// Where `size` bytes from `offset` end, if that's still inside a file of `len` bytes.
+fn span_end(offset: u64, size: u64, len: u64) -> Option<u64> {
+    offset.checked_add(size).filter(|end| *end <= len)
+}
+
+/// Maps `e_machine` to the architecture names used by AppImages and `std::env::consts::ARCH`.
+pub fn machine_arch(machine: u16) -> Option<&'static str> {
+    match machine {
+        0x03 => Some("x86"),
+        0x28 => Some("arm"),
+        0x3E => Some("x86_64"),
+        0xB7 => Some("aarch64"),
+        0xF3 => Some("riscv64"),
+        _ => None,
+    }
+}
+
+/// Names architectures show up under in asset names, most specific first so
+/// `x86_64` isn't taken for `x86` and `armhf` isn't taken for `arm`.
+const ARCH_TOKENS: [(&str, &[&str]); 5] = [
+    ("x86_64", &["x86_64", "x86-64", "amd64", "x64"]),
+    ("aarch64", &["aarch64", "arm64", "armv8"]),
+    ("riscv64", &["riscv64"]),
+    (
+        "arm",
+        &["armhf", "armv7l", "armv7", "armel", "arm32", "arm"],
+    ),
+    ("x86", &["i386", "i686", "x86", "ia32"]),
+];
+
+/// The architecture zap-rs runs on, named like [`machine_arch`] does.
+pub fn host_arch() -> &'static str {
+    std::env::consts::ARCH
+}
+
+fn contains_token(name: &str, token: &str) -> bool {
+    name.match_indices(token).any(|(pos, _)| {
+        let before = name[..pos].chars().next_back();
+        let after = name[pos + token.len()..].chars().next();
+
+        !before.is_some_and(|c| c.is_ascii_alphanumeric())
+            && !after.is_some_and(|c| c.is_ascii_alphanumeric())
+    })
+}
+
+/// Guesses which architecture a release asset is built for from its name.
+pub fn asset_arch(name: &str) -> Option<&'static str> {
+    let name = name.to_lowercase();
+
+    ARCH_TOKENS
+        .iter()
+        .find(|(_, tokens)| tokens.iter().any(|token| contains_token(&name, token)))
+        .map(|(arch, _)| *arch)
+}
+
+/// Refuses AppImages built for another architecture than the one we're running on.
+pub async fn verify_arch(path: &Path) -> Result<()> {
+    match ElfInfo::read(path).await?.arch() {
+        Some(arch) if arch != host_arch() => Err(Error::UnsupportedArch {
+            expected: host_arch().to_string(),
+            actual: arch.to_string(),
+        }),
+        _ => Ok(()),
+    }
+}
+
+impl ElfInfo {
+    pub async fn read(path: &Path) -> Result<Self> {
+        let mut file = fs::File::open(path).await?;
+        let len = file.metadata().await?.len();
+
+        let header = read_at(&mut file, 0, 64).await?;
+        if &header[..4] != b"\x7fELF" {
+            return Err(Error::InvalidAppImage);
+        }
+
+        let is_64 = header[4] == 2;
+        let little_endian = header[5] == 1;
+        let reader = Reader {
+            bytes: &header,
+            little_endian,
+        };
+
+        let machine = reader.u16(18)?;
+        let (phoff, shoff) = if is_64 {
+            (reader.u64(32)?, reader.u64(40)?)
+        } else {
+            (reader.u32(28)? as u64, reader.u32(32)? as u64)
+        };
+        let (phentsize, phnum, shentsize, shnum, shstrndx) = if is_64 {
+            (
+                reader.u16(54)?,
+                reader.u16(56)?,
+                reader.u16(58)?,
+                reader.u16(60)?,
+                reader.u16(62)?,
+            )
+        } else {
+            (
+                reader.u16(42)?,
+                reader.u16(44)?,
+                reader.u16(46)?,
+                reader.u16(48)?,
+                reader.u16(50)?,
+            )
+        };
+
+        let section_headers_len = shentsize as u64 * shnum as u64;
+        let mut end = span_end(shoff, section_headers_len, len).ok_or(Error::InvalidAppImage)?;
+
+        // Program headers tell whether the runtime is dynamically linked
+        let mut interpreter = false;
+        let program_headers_len = phentsize as usize * phnum as usize;
+        if span_end(phoff, program_headers_len as u64, len).is_some() {
+            let program_headers = read_at(&mut file, phoff, program_headers_len).await?;
+            let reader = Reader {
+                bytes: &program_headers,
+                little_endian,
+            };
+            for i in 0..phnum as usize {
+                let base = i * phentsize as usize;
+                // PT_INTERP
+                if reader.u32(base)? == 3 {
+                    interpreter = true;
+                }
+                let (offset, filesz) = if is_64 {
+                    (reader.u64(base + 8)?, reader.u64(base + 32)?)
+                } else {
+                    (reader.u32(base + 4)? as u64, reader.u32(base + 16)? as u64)
+                };
+                // Segments claiming to reach past the file can't tell where it ends
+                if let Some(segment_end) = span_end(offset, filesz, len) {
+                    end = end.max(segment_end);
+                }
+            }
+        }
+
+        let mut sections = vec![];
+        if shnum > 0 {
+            let section_headers = read_at(&mut file, shoff, section_headers_len as usize).await?;
+            let reader = Reader {
+                bytes: &section_headers,
+                little_endian,
+            };
+
+            let mut raw_sections = vec![];
+            for i in 0..shnum as usize {
+                let base = i * shentsize as usize;
+                let name = reader.u32(base)?;
+                let kind = reader.u32(base + 4)?;
+                let (offset, size) = if is_64 {
+                    (reader.u64(base + 24)?, reader.u64(base + 32)?)
+                } else {
+                    (reader.u32(base + 16)? as u64, reader.u32(base + 20)? as u64)
+                };
+                // SHT_NOBITS sections don't take space in the file
+                if kind != 8
+                    && let Some(section_end) = span_end(offset, size, len)
+                {
+                    end = end.max(section_end);
+                }
+                raw_sections.push((name, offset, size));
+            }
+
+            let names = match raw_sections.get(shstrndx as usize) {
+                Some((_, offset, size)) if span_end(*offset, *size, len).is_some() => {
+                    read_at(&mut file, *offset, *size as usize).await?
+                }
+                _ => vec![],
+            };
+
+            for (name, offset, size) in raw_sections {
+                let name = names
+                    .get(name as usize..)
+                    .and_then(|rest| rest.split(|b| *b == 0).next())
+                    .map(|name| String::from_utf8_lossy(name).to_string())
+                    .unwrap_or_default();
+                sections.push(ElfSection { name, offset, size });
+            }
+        }
+
+        Ok(Self {
+            is_64,
+            little_endian,
+            machine,
+            interpreter,
+            sections,
+            end,
+        })
+    }
+    pub fn arch(&self) -> Option<&'static str> {
+        machine_arch(self.machine)
+    }
+    pub fn section(&self, name: &str) -> Option<&ElfSection> {
+        self.sections.iter().find(|section| section.name == name)
+    }
+    pub async fn section_data(&self, path: &Path, name: &str) -> Result<Option<Vec<u8>>> {
+        let Some(section) = self.section(name) else {
+            return Ok(None);
+        };
+
+        let mut file = fs::File::open(path).await?;
+        let len = file.metadata().await?.len();
+
+        // Only small ones like `.upd_info` or `.sha256_sig` are read, a huge size means a broken file
+        if section.size > MAX_SECTION_SIZE || span_end(section.offset, section.size, len).is_none()
+        {
+            return Err(Error::InvalidAppImage);
+        }
+
+        Ok(Some(
+            read_at(&mut file, section.offset, section.size as usize).await?,
+        ))
+    }
+    /// A section's content as text, without the NUL padding. Empty sections count as missing.
+    pub async fn section_string(&self, path: &Path, name: &str) -> Result<Option<String>> {
+        Ok(self.section_data(path, name).await?.and_then(|data| {
+            let text = String::from_utf8_lossy(&data)
+                .trim_matches(char::from(0))
+                .trim()
+                .to_string();
+            (!text.is_empty()).then_some(text)
+        }))
+    }
+}
+
+/// The update information embedded by appimagetool, e.g. `gh-releases-zsync|owner|repo|latest|*.zsync`.
+pub async fn read_update_info(path: &Path) -> Result<Option<String>> {
+    ElfInfo::read(path)
+        .await?
+        .section_string(path, ".upd_info")
+        .await
+}
@@ -1,4 +1,5 @@
 use derive_more::From;
+use std::path::PathBuf;
 
 pub type Result<T> = core::result::Result<T, Error>;
 
@@ -6,13 +7,52 @@ pub type Result<T> = core::result::Result<T, Error>;
 pub enum Error {
     InvalidPath,
     NotFound(String),
+    FileNotFound(PathBuf),
     Download {
         url: String,
         source: reqwest::Error,
     },
     InvalidAppImage,
+    InvalidDesktopEntry(String),
     InvalidSlug(String),
     CantUpdatePkg,
+    RecipeNotFound(String),
+    RegistryNotFound(String),
+    RegistryRefresh(String),
+    InvalidRegistryName(String),
+    ChecksumMismatch {
+        expected: String,
+        actual: String,
+    },
+    InvalidEnvVar(String),
+    InvalidAlias(String),
+    InvalidSandboxDevice(String),
+    SandboxUnavailable(String),
+    UnsquashfsUnavailable,
+    FileCollision {
+        path: PathBuf,
+        owner: Option<String>,
+    },
+    UnsupportedArch {
+        expected: String,
+        actual: String,
+    },
+    HomeNotFound,
+    NoWriteAccess(PathBuf),
+    StoreLocked(PathBuf),
+    IndexTooNew {
+        appname: String,
+        version: u32,
+    },
+    InvalidIndexEntry {
+        appname: String,
+        reason: String,
+    },
+    NotSystemWide(String),
+    TarUnavailable,
+    NoPortableDirs(String),
+    PortableBackupFailed(PathBuf),
+    InvalidPortableBackup(PathBuf),
 
     #[from]
     Io(std::io::Error),
@@ -23,6 +63,15 @@ pub enum Error {
     #[from]
     Http(reqwest::Error),
 
+    #[from]
+    Xml(roxmltree::Error),
+
+    #[from]
+    Toml(toml::de::Error),
+
+    #[from]
+    Sqlite(rusqlite::Error),
+
     #[from]
     EnvVar(std::env::VarError),
 
@@ -43,7 +92,47 @@ impl core::fmt::Display for Error {
             Error::NotFound(name) => write!(fmt, "Application '{name}' not found"),
             Error::Json(e) => write!(fmt, "JSON error: {e}"),
             Error::Http(e) => write!(fmt, "HTTP error: {e}"),
+            Error::Xml(e) => write!(fmt, "XML error: {e}"),
+            Error::Toml(e) => write!(fmt, "Config error: {e}"),
+            Error::Sqlite(e) => write!(fmt, "Index database error: {e}"),
             Error::EnvVar(e) => write!(fmt, "Environment variable error: {e}"),
+            Error::IndexTooNew { appname, version } => write!(
+                fmt,
+                "The index entry of {appname} was written by a newer zap-rs (schema version {version}), update zap-rs to manage it"
+            ),
+            Error::InvalidIndexEntry { appname, reason } => {
+                write!(fmt, "The index entry of {appname} can't be read: {reason}")
+            }
+            Error::StoreLocked(path) => write!(
+                fmt,
+                "Another zap-rs is running (locked {}), try again once it's done or pass --wait",
+                path.display()
+            ),
+            Error::NoWriteAccess(path) => write!(
+                fmt,
+                "Can't write to {}, installing for all users usually needs root (try sudo)",
+                path.display()
+            ),
+            Error::NotSystemWide(what) => {
+                write!(fmt, "{what} aren't available for system-wide installs")
+            }
+            Error::TarUnavailable => write!(fmt, "tar isn't installed, it's needed for backups"),
+            Error::NoPortableDirs(appname) => {
+                write!(fmt, "{appname} doesn't have any portable directories")
+            }
+            Error::PortableBackupFailed(path) => {
+                write!(fmt, "Couldn't write the backup to '{}'", path.display())
+            }
+            Error::InvalidPortableBackup(path) => write!(
+                fmt,
+                "'{}' isn't a backup of portable directories",
+                path.display()
+            ),
+            Error::HomeNotFound => write!(
+                fmt,
+                "Couldn't find the home directory, HOME is unset and there's no passwd entry"
+            ),
+            Error::FileNotFound(path) => write!(fmt, "File '{}' not found", path.display()),
             Error::InvalidPath => write!(fmt, "Invalid path provided"),
             Error::CantUpdatePkg => write!(fmt, "Can't update package"),
             Error::IndicatifTemplate(e) => write!(fmt, "Progress bar template error: {e}"),
@@ -66,7 +155,57 @@ impl core::fmt::Display for Error {
             Error::InvalidAppImage => {
                 write!(fmt, "Invalid AppImage")
             }
+            Error::InvalidDesktopEntry(msg) => write!(fmt, "Invalid desktop entry: {msg}"),
             Error::InvalidSlug(slug) => write!(fmt, "Invalid repository slug {slug}"),
+            Error::RecipeNotFound(name) => write!(
+                fmt,
+                "No recipe named '{name}' found, pass --from or add a registry providing it"
+            ),
+            Error::RegistryNotFound(name) => write!(fmt, "Registry '{name}' not found"),
+            Error::RegistryRefresh(name) => write!(fmt, "Failed to refresh registry '{name}'"),
+            Error::InvalidRegistryName(name) => write!(
+                fmt,
+                "Invalid registry name '{name}', it can't be empty, '.', '..' or contain '/'"
+            ),
+            Error::ChecksumMismatch { expected, actual } => {
+                write!(fmt, "Checksum mismatch: expected {expected}, got {actual}")
+            }
+            Error::InvalidEnvVar(var) => write!(
+                fmt,
+                "Invalid environment variable '{var}', expected KEY=VALUE"
+            ),
+            Error::InvalidAlias(alias) => write!(fmt, "Invalid alias '{alias}'"),
+            Error::InvalidSandboxDevice(device) => write!(
+                fmt,
+                "Unknown device '{device}', use dri, snd, video or a /dev path"
+            ),
+            Error::UnsquashfsUnavailable => write!(
+                fmt,
+                "unsquashfs isn't installed, install squashfs-tools to read the AppImage's files"
+            ),
+            Error::SandboxUnavailable(program) => {
+                write!(
+                    fmt,
+                    "{program} isn't installed, it's needed to sandbox the app"
+                )
+            }
+            Error::FileCollision {
+                path,
+                owner: Some(owner),
+            } => write!(
+                fmt,
+                "'{}' belongs to {owner}, pass --force to replace it",
+                path.display()
+            ),
+            Error::FileCollision { path, owner: None } => write!(
+                fmt,
+                "'{}' already exists and wasn't created by zap-rs, pass --force to replace it",
+                path.display()
+            ),
+            Error::UnsupportedArch { expected, actual } => write!(
+                fmt,
+                "This AppImage is built for {actual} and can't run on this {expected} machine"
+            ),
             Error::Octocrab(e) => write!(fmt, "Octocrab error: {e}"),
             Error::Dialoguer(e) => write!(fmt, "Dialoguer error: {e}"),
         }
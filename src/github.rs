@@ -1,61 +1,142 @@
 use dialoguer::FuzzySelect;
-use octocrab::models::repos::Asset;
+use octocrab::models::repos::{Asset, Release};
+use std::{io::IsTerminal, time::Duration};
 
-use crate::{AppImage, Error, Result};
+use crate::{AppImage, Config, Error, Result, asset_arch, host_arch};
 
-pub async fn get_github_release_url(appimage: &AppImage) -> Result<String> {
-    let octocrab = octocrab::instance();
+#[derive(Debug, PartialEq)]
+pub struct GithubUrl {
+    pub slug: String,
+    pub tag: Option<String>,
+    pub asset_pattern: Option<String>,
+    pub latest: bool,
+}
 
-    let (owner, repo) = appimage
-        .source
-        .meta
-        .url
-        .split_once('/')
-        .ok_or_else(|| Error::InvalidSlug(appimage.source.meta.url.to_string()))?;
-
-    let page = octocrab
-        .repos(owner, repo)
-        .releases()
-        .list()
-        .per_page(100)
-        .send()
-        .await?;
-
-    let mut tags: Vec<String> = vec![];
-
-    for releases in &page {
-        for asset in &releases.assets {
-            if asset.name.to_lowercase().ends_with(".appimage") {
-                tags.push(releases.tag_name.to_string());
-                break;
-            }
+/// Recognises `github.com/owner/repo` URLs, including release tag and asset download links.
+pub fn parse_github_url(url: &str) -> Option<GithubUrl> {
+    let path = url
+        .strip_prefix("https://")
+        .or_else(|| url.strip_prefix("http://"))
+        .unwrap_or(url);
+    let path = path
+        .strip_prefix("www.github.com/")
+        .or_else(|| path.strip_prefix("github.com/"))?;
+    let path = path.split(['?', '#']).next().unwrap_or(path);
+
+    let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+
+    let (owner, repo) = match segments.as_slice() {
+        [owner, repo, ..] => (*owner, repo.trim_end_matches(".git")),
+        _ => return None,
+    };
+
+    let mut github_url = GithubUrl {
+        slug: format!("{owner}/{repo}"),
+        tag: None,
+        asset_pattern: None,
+        latest: false,
+    };
+
+    match &segments[2..] {
+        [] | ["releases"] => {}
+        ["releases", "latest"] => github_url.latest = true,
+        ["releases", "tag", tag] => github_url.tag = Some(tag.to_string()),
+        ["releases", "latest", "download", asset] => {
+            github_url.latest = true;
+            github_url.asset_pattern = Some(asset.to_string());
+        }
+        ["releases", "download", tag, asset] => {
+            github_url.tag = Some(tag.to_string());
+            github_url.asset_pattern = Some(asset_pattern(asset, tag));
+        }
+        _ => return None,
+    }
+
+    Some(github_url)
+}
+
+/// Turns an asset name into a pattern that survives version bumps, e.g.
+/// `App-1.2-x86_64.AppImage` with tag `v1.2` becomes `App-*-x86_64.AppImage`.
+fn asset_pattern(asset: &str, tag: &str) -> String {
+    let version = tag.trim_start_matches(['v', 'V']);
+
+    if version.is_empty() || !asset.contains(version) {
+        return asset.to_string();
+    }
+
+    asset.replace(version, "*")
+}
+
+/// Case-insensitive glob match supporting `*` wildcards.
+pub fn matches_pattern(pattern: &str, name: &str) -> bool {
+    let pattern = pattern.to_lowercase();
+    let name = name.to_lowercase();
+
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or("");
+
+    let Some(mut rest) = name.strip_prefix(first) else {
+        return false;
+    };
+
+    let parts: Vec<&str> = parts.collect();
+
+    for (i, part) in parts.iter().enumerate() {
+        if i == parts.len() - 1 {
+            return rest.ends_with(part);
+        }
+        match rest.find(part) {
+            Some(pos) => rest = &rest[pos + part.len()..],
+            None => return false,
         }
     }
 
-    let tag_selection = FuzzySelect::new()
-        .with_prompt("Choose a release")
-        .items(&tags)
-        .max_length(7)
-        .vim_mode(true)
-        .interact()?;
-
-    let mut assets: Vec<Asset> = vec![];
-
-    for releases in page {
-        if releases.tag_name == tags[tag_selection] {
-            for asset in releases.assets {
-                if asset.name.to_lowercase().ends_with(".appimage") {
-                    assets.push(asset);
-                }
-            }
+    rest.is_empty()
+}
+
+fn appimage_assets(release: Release) -> Vec<Asset> {
+    release
+        .assets
+        .into_iter()
+        .filter(|asset| asset.name.to_lowercase().ends_with(".appimage"))
+        .collect()
+}
+
+/// How well an asset name fits this machine: its architecture, then no
+/// architecture at all, then anything else.
+pub fn arch_score(name: &str) -> u8 {
+    match asset_arch(name) {
+        Some(arch) if arch == host_arch() => 2,
+        None => 1,
+        Some(_) => 0,
+    }
+}
+
+fn select_asset(assets: Vec<Asset>, pattern: Option<&str>) -> Result<Asset> {
+    let mut assets = assets;
+
+    if let Some(pattern) = pattern {
+        let matching: Vec<Asset> = assets
+            .iter()
+            .filter(|asset| matches_pattern(pattern, &asset.name))
+            .cloned()
+            .collect();
+
+        if !matching.is_empty() {
+            assets = matching;
         }
     }
 
+    // Stable, so releases keep their own order among equally good matches
+    assets.sort_by_key(|asset| std::cmp::Reverse(arch_score(&asset.name)));
+
     let mut asset_selection: usize = 0;
 
-    if assets.len() > 1 {
+    // Without a terminal to ask on, the best match is as good a choice as any
+    if assets.len() > 1 && std::io::stdin().is_terminal() {
         asset_selection = FuzzySelect::new()
             .with_prompt("Choose an asset")
+            .default(0)
             .items(
                 &assets
                     .iter()
@@ -67,7 +148,85 @@ pub async fn get_github_release_url(appimage: &AppImage) -> Result<String> {
             .interact()?;
     }
 
-    let url = assets[asset_selection].browser_download_url.to_string();
+    assets
+        .into_iter()
+        .nth(asset_selection)
+        .ok_or(Error::InvalidAppImage)
+}
+
+/// Resolves the download URL of the release asset and records the chosen tag in the source.
+pub async fn get_github_release_url(appimage: &mut AppImage, config: &Config) -> Result<String> {
+    let timeout = Some(Duration::from_secs(config.network.timeout));
+    let mut builder = octocrab::Octocrab::builder()
+        .set_connect_timeout(timeout)
+        .set_read_timeout(timeout);
+    if let Some(token) = &config.network.github_token {
+        builder = builder.personal_token(token.clone());
+    }
+    let octocrab = builder.build()?;
+
+    let meta = &mut appimage.source.meta;
+
+    let (owner, repo) = meta
+        .url
+        .split_once('/')
+        .ok_or_else(|| Error::InvalidSlug(meta.url.to_string()))?;
+
+    // Sources that don't say otherwise follow the configured defaults, and without
+    // a terminal to ask on there's no choice but the latest release
+    let latest = meta.latest
+        || (meta.tag.is_none() && (config.sources.latest || !std::io::stdin().is_terminal()));
+
+    let release = if latest {
+        octocrab.repos(owner, repo).releases().get_latest().await?
+    } else if let Some(tag) = &meta.tag {
+        octocrab
+            .repos(owner, repo)
+            .releases()
+            .get_by_tag(tag)
+            .await?
+    } else {
+        let page = octocrab
+            .repos(owner, repo)
+            .releases()
+            .list()
+            .per_page(100)
+            .send()
+            .await?;
+
+        let mut releases: Vec<Release> = page
+            .into_iter()
+            .filter(|release| {
+                release
+                    .assets
+                    .iter()
+                    .any(|asset| asset.name.to_lowercase().ends_with(".appimage"))
+            })
+            .collect();
+
+        let tags: Vec<String> = releases
+            .iter()
+            .map(|release| release.tag_name.to_string())
+            .collect();
+
+        let tag_selection = FuzzySelect::new()
+            .with_prompt("Choose a release")
+            .items(&tags)
+            .max_length(7)
+            .vim_mode(true)
+            .interact()?;
+
+        releases.swap_remove(tag_selection)
+    };
+
+    meta.tag = Some(release.tag_name.to_string());
+
+    let asset = select_asset(
+        appimage_assets(release),
+        meta.asset_pattern
+            .as_deref()
+            .or(config.sources.asset_pattern.as_deref()),
+    )?;
 
-    Ok(url)
+    Ok(asset.browser_download_url.to_string())
 }
@@ -1,44 +1,246 @@
+use async_trait::async_trait;
+use serde_json::{Map, Value};
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 use tokio::fs;
 
-use crate::{AppImage, Error, Result, index_dir};
+use crate::{AppImage, Config, Error, IndexBackend, Result, SqliteIndex, write_atomically};
 
-#[derive(Debug, Default)]
-pub struct Index {}
+/// The layout index entries are written in.
+pub const INDEX_SCHEMA_VERSION: u32 = 1;
 
-impl Index {
-    pub fn new() -> Self {
-        Self {}
+/// `MIGRATIONS[n]` brings an entry from schema version `n` to `n + 1`.
+const MIGRATIONS: [fn(&mut Map<String, Value>); INDEX_SCHEMA_VERSION as usize] = [from_unversioned];
+
+/// Everything added to the layout before it was versioned has a default, so the version
+/// is all an entry from back then is missing.
+fn from_unversioned(_entry: &mut Map<String, Value>) {}
+
+/// Versions too big for a u32 are still too new, and must not wrap around to an old one.
+fn schema_version(entry: &Value) -> u32 {
+    entry
+        .get("schema_version")
+        .and_then(Value::as_u64)
+        .map_or(0, |version| u32::try_from(version).unwrap_or(u32::MAX))
+}
+
+/// Brings an entry up to the current layout, returning the version it had.
+fn migrate_entry(appname: &str, entry: &mut Value) -> Result<u32> {
+    let version = schema_version(entry);
+    if version > INDEX_SCHEMA_VERSION {
+        return Err(Error::IndexTooNew {
+            appname: appname.to_string(),
+            version,
+        });
+    }
+
+    let Some(object) = entry.as_object_mut() else {
+        return Err(Error::InvalidIndexEntry {
+            appname: appname.to_string(),
+            reason: "not a JSON object".to_string(),
+        });
+    };
+    for migration in &MIGRATIONS[version as usize..] {
+        migration(object);
+    }
+    object.insert("schema_version".to_string(), INDEX_SCHEMA_VERSION.into());
+
+    Ok(version)
+}
+
+/// Reads an entry in any layout this version knows about.
+pub fn parse_entry(appname: &str, content: &str) -> Result<AppImage> {
+    let invalid = |e: serde_json::Error| Error::InvalidIndexEntry {
+        appname: appname.to_string(),
+        reason: e.to_string(),
+    };
+
+    let mut entry: Value = serde_json::from_str(content).map_err(invalid)?;
+    migrate_entry(appname, &mut entry)?;
+
+    serde_json::from_value(entry).map_err(invalid)
+}
+
+pub fn content_schema_version(content: &str) -> u32 {
+    serde_json::from_str(content).map_or(0, |entry| schema_version(&entry))
+}
+
+/// Keeps a copy of an entry in an older layout before it's overwritten.
+pub async fn backup_entry(config: &Config, appname: &str, content: &str) -> Result<()> {
+    let version = content_schema_version(content);
+    if version >= INDEX_SCHEMA_VERSION {
+        return Ok(());
+    }
+
+    let backup_path = config
+        .index_backups_dir()
+        .join(format!("{appname}.v{version}.json"));
+    // The first backup is the one closest to the original
+    if !fs::try_exists(&backup_path).await? {
+        fs::create_dir_all(config.index_backups_dir()).await?;
+        fs::write(backup_path, content).await?;
+    }
+
+    Ok(())
+}
+
+/// Where the installed AppImages are recorded, by the name they were installed as.
+#[async_trait]
+pub trait IndexStore: fmt::Debug + Send + Sync {
+    /// The entry as it was stored, in whatever layout it was written in.
+    async fn read(&self, appname: &str) -> Result<String>;
+    async fn list(&self) -> Result<Vec<String>>;
+    async fn exists(&self, appname: &str) -> Result<bool>;
+    async fn add(&self, appimage: &AppImage, appname: &str) -> Result<()>;
+    async fn remove(&self, appname: &str) -> Result<()>;
+    /// The app that created `path`, or the directory it's in.
+    async fn owner_of(&self, path: &Path) -> Result<Option<String>>;
+
+    /// Reads an entry, migrated in memory if it's in an older layout.
+    async fn get(&self, appname: &str) -> Result<AppImage> {
+        parse_entry(appname, &self.read(appname).await?)
+    }
+    /// Every entry sorted by name, the ones that can't be read with their error.
+    async fn entries(&self) -> Result<Vec<(String, Result<AppImage>)>> {
+        let mut entries = vec![];
+        for appname in self.list().await? {
+            let appimage = self.get(&appname).await;
+            entries.push((appname, appimage));
+        }
+
+        Ok(entries)
+    }
+    /// Copies every readable entry of the JSON index over, replacing entries of the same name.
+    async fn import(&self, json: &JsonIndex) -> Result<usize> {
+        let mut imported = 0;
+        for (appname, appimage) in json.entries().await? {
+            match appimage {
+                Ok(appimage) => {
+                    self.add(&appimage, &appname).await?;
+                    imported += 1;
+                }
+                Err(e) => eprintln!("Skipping {appname}: {e}"),
+            }
+        }
+
+        Ok(imported)
+    }
+    /// Rewrites an entry in the current layout, returning the version it had if it was older.
+    async fn migrate(&self, appname: &str, dry_run: bool) -> Result<Option<u32>> {
+        let content = self.read(appname).await?;
+        let version = content_schema_version(&content);
+        if version == INDEX_SCHEMA_VERSION {
+            return Ok(None);
+        }
+
+        // Also makes sure the migrated entry can be read before anything is written
+        let appimage = parse_entry(appname, &content)?;
+        if !dry_run {
+            self.add(&appimage, appname).await?;
+        }
+
+        Ok(Some(version))
+    }
+}
+
+/// Opens the index backend picked in the config. The database is only created with `create`,
+/// until then the JSON index is still the one to read from.
+pub async fn open_index(config: Arc<Config>, create: bool) -> Result<Box<dyn IndexStore>> {
+    Ok(match config.index.backend {
+        IndexBackend::Sqlite if create || fs::try_exists(config.index_db_file()).await? => {
+            Box::new(SqliteIndex::open(config).await?)
+        }
+        IndexBackend::Json | IndexBackend::Sqlite => Box::new(JsonIndex::new(config)),
+    })
+}
+
+/// One JSON file per app in the index directory.
+#[derive(Debug)]
+pub struct JsonIndex {
+    config: Arc<Config>,
+}
+
+impl JsonIndex {
+    pub fn new(config: Arc<Config>) -> Self {
+        Self { config }
+    }
+    fn entry_path(&self, appname: &str) -> PathBuf {
+        self.config.index_dir().join(format!("{appname}.json"))
+    }
+}
+
+#[async_trait]
+impl IndexStore for JsonIndex {
+    async fn read(&self, appname: &str) -> Result<String> {
+        fs::read_to_string(self.entry_path(appname))
+            .await
+            .map_err(|e| {
+                if e.kind() == std::io::ErrorKind::NotFound {
+                    Error::NotFound(appname.to_string())
+                } else {
+                    Error::from(e)
+                }
+            })
     }
-    pub async fn get(&self, appname: &str) -> Result<AppImage> {
-        let index_file_path = index_dir()?.join(format!("{appname}.json"));
-        let index_file_content = fs::read_to_string(&index_file_path).await.map_err(|e| {
-            if e.kind() == std::io::ErrorKind::NotFound {
-                Error::NotFound(appname.to_string())
-            } else {
-                Error::from(e)
+    async fn list(&self) -> Result<Vec<String>> {
+        let mut appnames = vec![];
+
+        if !fs::try_exists(self.config.index_dir()).await? {
+            return Ok(appnames);
+        }
+
+        let mut entries = fs::read_dir(self.config.index_dir()).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            if entry.path().extension() == Some("json".as_ref())
+                && let Some(stem) = entry.path().file_stem().and_then(|s| s.to_str())
+            {
+                appnames.push(stem.to_string());
             }
-        })?;
-        let appimage: AppImage = serde_json::from_str(&index_file_content)?;
+        }
+        appnames.sort();
 
-        Ok(appimage)
+        Ok(appnames)
     }
-    pub fn exists(&self, executable: &str) -> Result<bool> {
-        Ok(index_dir()?.join(format!("{}.json", &executable)).exists())
+    async fn exists(&self, appname: &str) -> Result<bool> {
+        Ok(fs::try_exists(self.entry_path(appname)).await?)
     }
-    pub async fn add(&self, appimage: &AppImage, appname: &str) -> Result<()> {
-        fs::create_dir_all(&index_dir()?).await?;
+    async fn add(&self, appimage: &AppImage, appname: &str) -> Result<()> {
+        fs::create_dir_all(self.config.index_dir()).await?;
 
-        let index_file = &index_dir()?.join(format!("{appname}.json"));
+        let index_file = self.entry_path(appname);
+        if let Ok(content) = fs::read_to_string(&index_file).await {
+            backup_entry(&self.config, appname, &content).await?;
+        }
 
         let json = serde_json::to_string_pretty(appimage)?;
-        fs::write(index_file, json).await?;
+        write_atomically(&index_file, json.as_bytes()).await?;
 
         Ok(())
     }
-    pub async fn remove(&self, appname: &str) -> Result<()> {
-        let index_file_path = index_dir()?.join(format!("{appname}.json"));
-        fs::remove_file(index_file_path).await?;
+    async fn remove(&self, appname: &str) -> Result<()> {
+        fs::remove_file(self.entry_path(appname)).await?;
 
         Ok(())
     }
+    async fn owner_of(&self, path: &Path) -> Result<Option<String>> {
+        for (appname, appimage) in self.entries().await? {
+            let Ok(appimage) = appimage else {
+                continue;
+            };
+
+            // Directories like an extracted AppDir own everything in them
+            if appimage
+                .owned_files(&self.config)
+                .iter()
+                .any(|file| path.starts_with(file))
+            {
+                return Ok(Some(appname));
+            }
+        }
+
+        Ok(None)
+    }
 }
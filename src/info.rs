@@ -0,0 +1,185 @@
+use chrono::{DateTime, Utc};
+use indicatif::HumanBytes;
+use serde::Serialize;
+use std::{collections::BTreeMap, fmt, path::PathBuf};
+use tokio::fs;
+
+use crate::{AppImage, Config, Result, RunMode, SandboxProfile, SymlinkManager, read_update_info};
+
+/// Everything known about an installed AppImage, as shown by `zap-rs info`.
+#[derive(Debug, Serialize)]
+pub struct AppInfo {
+    pub appname: String,
+    pub name: Option<String>,
+    pub summary: Option<String>,
+    pub developer: Option<String>,
+    pub homepage: Option<String>,
+    pub license: Option<String>,
+    pub version: Option<String>,
+    pub source: String,
+    pub url: String,
+    pub tag: Option<String>,
+    pub file_path: PathBuf,
+    pub run_mode: RunMode,
+    pub env: BTreeMap<String, String>,
+    pub args: Vec<String>,
+    pub aliases: Vec<String>,
+    pub portable_home: Option<PathBuf>,
+    pub portable_config: Option<PathBuf>,
+    pub sandbox: Option<SandboxProfile>,
+    pub size: Option<u64>,
+    pub sha256: Option<String>,
+    pub installed_at: Option<DateTime<Utc>>,
+    pub updated_at: Option<DateTime<Utc>>,
+    pub symlinks: Vec<PathBuf>,
+    pub integrated: bool,
+    pub integration_files: Vec<PathBuf>,
+    pub update_info: Option<String>,
+}
+
+impl AppInfo {
+    pub async fn new(
+        appname: &str,
+        appimage: &AppImage,
+        config: &Config,
+        symlink_manager: &SymlinkManager,
+    ) -> Result<Self> {
+        let metainfo = appimage.metainfo.clone().unwrap_or_default();
+
+        let size = fs::metadata(&appimage.file_path)
+            .await
+            .ok()
+            .map(|metadata| metadata.len());
+
+        let symlinks = symlink_manager.links(appimage).await?;
+
+        let integration_files = appimage
+            .integration
+            .iter()
+            .flat_map(|integration| integration.files())
+            .cloned()
+            .collect();
+
+        // The file may be gone or not be an AppImage we can read, that's not worth failing over
+        let update_info = read_update_info(&appimage.file_path).await.ok().flatten();
+
+        Ok(Self {
+            appname: appname.to_string(),
+            name: metainfo.name,
+            summary: metainfo.summary,
+            developer: metainfo.developer,
+            homepage: metainfo.homepage,
+            license: metainfo.license,
+            version: appimage.version.clone(),
+            source: appimage.source.identifier.to_string(),
+            url: appimage.source.meta.url.to_string(),
+            tag: appimage.source.meta.tag.clone(),
+            file_path: appimage.file_path.clone(),
+            run_mode: appimage.run_mode,
+            env: appimage.env.clone(),
+            args: appimage.args.clone(),
+            aliases: appimage.aliases.clone(),
+            portable_home: appimage
+                .portable
+                .home
+                .then(|| appimage.portable_home_path(config)),
+            portable_config: appimage
+                .portable
+                .config
+                .then(|| appimage.portable_config_path(config)),
+            sandbox: appimage.sandbox.clone(),
+            size,
+            sha256: appimage.sha256.clone(),
+            installed_at: appimage.installed_at,
+            updated_at: appimage.updated_at,
+            symlinks,
+            integrated: appimage.is_integrated(config),
+            integration_files,
+            update_info,
+        })
+    }
+}
+
+fn format_time(time: &DateTime<Utc>) -> String {
+    time.format("%Y-%m-%d %H:%M:%S UTC").to_string()
+}
+
+impl fmt::Display for AppInfo {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let fields = [
+            ("Name", self.name.clone().or(Some(self.appname.to_string()))),
+            ("Summary", self.summary.clone()),
+            ("Version", self.version.clone()),
+            ("Developer", self.developer.clone()),
+            ("Homepage", self.homepage.clone()),
+            ("License", self.license.clone()),
+            ("Source", Some(self.source.to_string())),
+            ("URL", Some(self.url.to_string())),
+            ("Tag", self.tag.clone()),
+            ("File", Some(self.file_path.display().to_string())),
+            (
+                "Aliases",
+                (!self.aliases.is_empty()).then(|| self.aliases.join(", ")),
+            ),
+            ("Run mode", Some(self.run_mode.to_string())),
+            (
+                "Portable home",
+                self.portable_home
+                    .as_ref()
+                    .map(|path| path.display().to_string()),
+            ),
+            (
+                "Portable config",
+                self.portable_config
+                    .as_ref()
+                    .map(|path| path.display().to_string()),
+            ),
+            (
+                "Arguments",
+                (!self.args.is_empty()).then(|| self.args.join(" ")),
+            ),
+            (
+                "Sandbox",
+                self.sandbox.as_ref().map(|sandbox| sandbox.to_string()),
+            ),
+            ("Size", self.size.map(|size| HumanBytes(size).to_string())),
+            ("SHA256", self.sha256.clone()),
+            ("Installed", self.installed_at.as_ref().map(format_time)),
+            ("Updated", self.updated_at.as_ref().map(format_time)),
+            (
+                "Integrated",
+                Some(if self.integrated { "yes" } else { "no" }.to_string()),
+            ),
+            ("Update info", self.update_info.clone()),
+        ];
+
+        for (label, value) in fields {
+            if let Some(value) = value {
+                writeln!(fmt, "{label}: {value}")?;
+            }
+        }
+
+        if !self.env.is_empty() {
+            writeln!(fmt, "Environment:")?;
+            for (key, value) in &self.env {
+                writeln!(fmt, "  {key}={value}")?;
+            }
+        }
+
+        for (label, paths) in [
+            ("Symlinks", &self.symlinks),
+            ("Integration files", &self.integration_files),
+        ] {
+            if paths.is_empty() {
+                continue;
+            }
+
+            writeln!(fmt, "{label}:")?;
+            for path in paths {
+                writeln!(fmt, "  - {}", path.display())?;
+            }
+        }
+
+        Ok(())
+    }
+}
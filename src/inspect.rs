@@ -0,0 +1,311 @@
+use serde::Serialize;
+use std::{
+    fmt,
+    io::SeekFrom,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+use tokio::{
+    fs,
+    io::{AsyncReadExt, AsyncSeekExt},
+};
+
+use crate::{ElfInfo, Error, Metainfo, Result, read_update_info};
+
+const ICON_EXTENSIONS: [&str; 3] = [".png", ".svg", ".xpm"];
+
+#[derive(Debug, Serialize)]
+pub struct Runtime {
+    /// Size of the runtime, the filesystem image starts right after it
+    pub size: u64,
+    pub static_linked: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Squashfs {
+    pub offset: u64,
+    pub compression: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SignatureStatus {
+    Signed,
+    Unsigned,
+    /// The runtime predates signature support
+    Unsupported,
+}
+
+/// What we can tell about an AppImage without ever executing it.
+#[derive(Debug, Serialize)]
+pub struct Inspection {
+    pub path: PathBuf,
+    pub appimage_type: Option<u8>,
+    pub arch: Option<String>,
+    pub runtime: Runtime,
+    pub squashfs: Option<Squashfs>,
+    pub update_info: Option<String>,
+    pub signature: SignatureStatus,
+    /// Whether `unsquashfs` was around to look inside the filesystem image
+    pub contents_available: bool,
+    pub desktop_entry: Option<String>,
+    pub icons: Vec<String>,
+    pub metainfo: Option<Metainfo>,
+}
+
+fn compression_name(id: u16) -> String {
+    match id {
+        1 => "gzip",
+        2 => "lzma",
+        3 => "lzo",
+        4 => "xz",
+        5 => "lz4",
+        6 => "zstd",
+        _ => "unknown",
+    }
+    .to_string()
+}
+
+async fn read_squashfs(path: &Path, offset: u64) -> Result<Option<Squashfs>> {
+    let mut file = fs::File::open(path).await?;
+    let mut superblock = [0u8; 22];
+
+    file.seek(SeekFrom::Start(offset)).await?;
+    if file.read_exact(&mut superblock).await.is_err() || &superblock[..4] != b"hsqs" {
+        return Ok(None);
+    }
+
+    Ok(Some(Squashfs {
+        offset,
+        compression: compression_name(u16::from_le_bytes([superblock[20], superblock[21]])),
+    }))
+}
+
+fn unsquashfs(path: &Path, offset: u64, args: &[&str]) -> Option<String> {
+    let output = Command::new("unsquashfs")
+        .arg("-o")
+        .arg(offset.to_string())
+        .args(args)
+        .arg(path)
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+fn unsquashfs_list(path: &Path, offset: u64) -> Option<Vec<String>> {
+    unsquashfs(path, offset, &["-l"]).map(|listing| {
+        listing
+            .lines()
+            .filter_map(|line| line.strip_prefix("squashfs-root/"))
+            .map(str::to_string)
+            .collect()
+    })
+}
+
+fn unsquashfs_cat(path: &Path, offset: u64, file: &str) -> Option<String> {
+    // `-cat` takes its file list after the image
+    let output = Command::new("unsquashfs")
+        .arg("-o")
+        .arg(offset.to_string())
+        .arg("-cat")
+        .arg(path)
+        .arg(file)
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+
+    (output.status.success() && !output.stdout.is_empty())
+        .then(|| String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+impl Inspection {
+    pub async fn new(path: &Path) -> Result<Self> {
+        let mut header = [0u8; 11];
+        fs::File::open(path)
+            .await?
+            .read_exact(&mut header)
+            .await
+            .map_err(|_| Error::InvalidAppImage)?;
+
+        let elf = ElfInfo::read(path).await?;
+
+        let appimage_type = (&header[8..10] == b"AI").then_some(header[10]);
+
+        let update_info = read_update_info(path).await?;
+
+        let signature = match elf.section(".sha256_sig") {
+            None => SignatureStatus::Unsupported,
+            Some(_) => match elf.section_data(path, ".sha256_sig").await? {
+                Some(data) if data.iter().any(|b| *b != 0) => SignatureStatus::Signed,
+                _ => SignatureStatus::Unsigned,
+            },
+        };
+
+        // Type 1 AppImages are ISO 9660 images, only type 2 ones carry a SquashFS
+        let squashfs = match appimage_type {
+            Some(2) => read_squashfs(path, elf.end).await?,
+            _ => None,
+        };
+
+        let mut inspection = Self {
+            path: path.to_path_buf(),
+            appimage_type,
+            arch: elf.arch().map(str::to_string),
+            runtime: Runtime {
+                size: elf.end,
+                static_linked: !elf.interpreter,
+            },
+            squashfs: None,
+            update_info,
+            signature,
+            contents_available: false,
+            desktop_entry: None,
+            icons: vec![],
+            metainfo: None,
+        };
+
+        if let Some(squashfs) = squashfs {
+            inspection.inspect_contents(squashfs.offset);
+            inspection.squashfs = Some(squashfs);
+        }
+
+        Ok(inspection)
+    }
+    fn inspect_contents(&mut self, offset: u64) {
+        let Some(files) = unsquashfs_list(&self.path, offset) else {
+            return;
+        };
+        self.contents_available = true;
+
+        // The desktop file at the root is usually a symlink, so try the real one first
+        let desktop_files = files
+            .iter()
+            .filter(|file| {
+                file.starts_with("usr/share/applications/") && file.ends_with(".desktop")
+            })
+            .chain(
+                files
+                    .iter()
+                    .filter(|file| !file.contains('/') && file.ends_with(".desktop")),
+            );
+        for file in desktop_files {
+            if let Some(content) = unsquashfs_cat(&self.path, offset, file) {
+                self.desktop_entry = Some(content);
+                break;
+            }
+        }
+
+        self.icons = files
+            .iter()
+            .filter(|file| {
+                *file == ".DirIcon"
+                    || ((file.starts_with("usr/share/icons/")
+                        || file.starts_with("usr/share/pixmaps/")
+                        || !file.contains('/'))
+                        && ICON_EXTENSIONS.iter().any(|ext| file.ends_with(ext)))
+            })
+            .cloned()
+            .collect();
+
+        self.metainfo = files
+            .iter()
+            .filter(|file| {
+                (file.starts_with("usr/share/metainfo/") || file.starts_with("usr/share/appdata/"))
+                    && file.ends_with(".xml")
+            })
+            .filter_map(|file| unsquashfs_cat(&self.path, offset, file))
+            .find_map(|content| Metainfo::parse(&content).ok());
+    }
+}
+
+impl fmt::Display for Inspection {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(fmt, "File: {}", self.path.display())?;
+        match self.appimage_type {
+            Some(kind) => writeln!(fmt, "AppImage type: {kind}")?,
+            None => writeln!(fmt, "AppImage type: not an AppImage")?,
+        }
+        writeln!(
+            fmt,
+            "Architecture: {}",
+            self.arch.as_deref().unwrap_or("unknown")
+        )?;
+        writeln!(
+            fmt,
+            "Runtime: {} bytes, {}",
+            self.runtime.size,
+            if self.runtime.static_linked {
+                "statically linked"
+            } else {
+                "dynamically linked"
+            }
+        )?;
+        if let Some(squashfs) = &self.squashfs {
+            writeln!(
+                fmt,
+                "SquashFS: {} compression at offset {}",
+                squashfs.compression, squashfs.offset
+            )?;
+        }
+        writeln!(
+            fmt,
+            "Update info: {}",
+            self.update_info.as_deref().unwrap_or("none")
+        )?;
+        writeln!(
+            fmt,
+            "Signature: {}",
+            match self.signature {
+                SignatureStatus::Signed => "signed",
+                SignatureStatus::Unsigned => "unsigned",
+                SignatureStatus::Unsupported => "not supported by the runtime",
+            }
+        )?;
+
+        if self.squashfs.is_some() && !self.contents_available {
+            writeln!(
+                fmt,
+                "Contents: install squashfs-tools (unsquashfs) to look inside"
+            )?;
+            return Ok(());
+        }
+
+        if let Some(metainfo) = &self.metainfo {
+            let fields = [
+                ("Name", &metainfo.name),
+                ("Summary", &metainfo.summary),
+                ("Version", &metainfo.version),
+                ("Developer", &metainfo.developer),
+                ("Homepage", &metainfo.homepage),
+                ("License", &metainfo.license),
+            ];
+            writeln!(fmt, "Metainfo:")?;
+            for (label, value) in fields {
+                if let Some(value) = value {
+                    writeln!(fmt, "  {label}: {value}")?;
+                }
+            }
+        }
+
+        if !self.icons.is_empty() {
+            writeln!(fmt, "Icons:")?;
+            for icon in &self.icons {
+                writeln!(fmt, "  - {icon}")?;
+            }
+        }
+
+        if let Some(desktop_entry) = &self.desktop_entry {
+            writeln!(fmt, "Desktop entry:")?;
+            for line in desktop_entry.lines() {
+                writeln!(fmt, "  {line}")?;
+            }
+        }
+
+        Ok(())
+    }
+}
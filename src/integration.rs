@@ -0,0 +1,380 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+use tokio::{fs, io::AsyncReadExt};
+
+use crate::{Config, DesktopEntry, Result};
+
+const ICON_EXTENSIONS: [&str; 3] = ["png", "svg", "xpm"];
+
+/// Runs a cache-rebuilding tool if it's installed, these are optional for integration.
+pub fn run_if_available(program: &str, args: &[&str]) {
+    let _ = Command::new(program)
+        .args(args)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status();
+}
+
+/// Namespaced so our icons never clash with ones installed by the system or other apps.
+pub fn icon_name(executable: &str) -> String {
+    format!("zap-rs-{executable}")
+}
+
+fn icon_stem(icon: &str) -> &str {
+    ICON_EXTENSIONS
+        .iter()
+        .find_map(|ext| icon.strip_suffix(&format!(".{ext}")))
+        .unwrap_or(icon)
+}
+
+/// The sizes the hicolor theme's index lists, icons in other directories are never found.
+const HICOLOR_SIZES: [u32; 13] = [16, 22, 24, 32, 36, 48, 64, 72, 96, 128, 192, 256, 512];
+
+/// The listed size directory closest to an icon's real size.
+fn hicolor_size(width: u32, height: u32) -> String {
+    let size = width.max(height);
+    let nearest = HICOLOR_SIZES
+        .iter()
+        .min_by_key(|listed| listed.abs_diff(size))
+        .unwrap_or(&48);
+
+    format!("{nearest}x{nearest}")
+}
+
+/// The width and height from the values line of an XPM, the first string in the file.
+fn xpm_size(content: &[u8]) -> Option<(u32, u32)> {
+    let content = String::from_utf8_lossy(content);
+    let values = content.split('"').nth(1)?;
+    let mut numbers = values.split_whitespace().map(|n| n.parse().ok());
+
+    Some((numbers.next()??, numbers.next()??))
+}
+
+/// Figures out the hicolor size directory of a loose icon from its content.
+async fn icon_size(path: &Path) -> Option<(String, &'static str)> {
+    let mut header = [0u8; 1024];
+    let mut file = fs::File::open(path).await.ok()?;
+    let read = file.read(&mut header).await.ok()?;
+    let header = &header[..read];
+
+    if read >= 24 && header.starts_with(b"\x89PNG\r\n\x1a\n") && &header[12..16] == b"IHDR" {
+        let width = u32::from_be_bytes(header[16..20].try_into().ok()?);
+        let height = u32::from_be_bytes(header[20..24].try_into().ok()?);
+        return Some((hicolor_size(width, height), "png"));
+    }
+
+    if header.starts_with(b"/* XPM */") {
+        let (width, height) = xpm_size(header)?;
+        return Some((hicolor_size(width, height), "xpm"));
+    }
+
+    if header.starts_with(b"<") || path.extension() == Some("svg".as_ref()) {
+        return Some(("scalable".to_string(), "svg"));
+    }
+
+    None
+}
+
+async fn hicolor_icons(squashfs: &Path, icon: &str) -> Result<BTreeMap<String, PathBuf>> {
+    let mut icons = BTreeMap::new();
+    let hicolor = squashfs.join("usr/share/icons/hicolor");
+
+    if !fs::try_exists(&hicolor).await? {
+        return Ok(icons);
+    }
+
+    let mut sizes = fs::read_dir(&hicolor).await?;
+    while let Some(size) = sizes.next_entry().await? {
+        let Some(size_name) = size.file_name().to_str().map(str::to_string) else {
+            continue;
+        };
+
+        for ext in ICON_EXTENSIONS {
+            let path = size.path().join(format!("apps/{icon}.{ext}"));
+            if fs::metadata(&path).await.is_ok() {
+                icons.insert(size_name, path);
+                break;
+            }
+        }
+    }
+
+    Ok(icons)
+}
+
+/// Installs every available size of the app's icon into the user's hicolor theme, falling
+/// back to `usr/share/pixmaps` and the AppImage's `.DirIcon`. Returns the installed files.
+pub async fn install_icons(
+    config: &Config,
+    squashfs: &Path,
+    icon: Option<&str>,
+    executable: &str,
+) -> Result<Vec<PathBuf>> {
+    let mut icons = match icon {
+        Some(icon) => hicolor_icons(squashfs, icon_stem(icon)).await?,
+        None => BTreeMap::new(),
+    };
+
+    let mut loose_icons = vec![];
+    if let Some(icon) = icon.map(icon_stem) {
+        for ext in ICON_EXTENSIONS {
+            loose_icons.push(squashfs.join(format!("usr/share/pixmaps/{icon}.{ext}")));
+            loose_icons.push(squashfs.join(format!("{icon}.{ext}")));
+        }
+    }
+    loose_icons.push(squashfs.join(".DirIcon"));
+
+    for path in loose_icons {
+        if fs::metadata(&path).await.is_err() {
+            continue;
+        }
+        if let Some((size, ext)) = icon_size(&path).await
+            && !icons.contains_key(&size)
+        {
+            // Keep track of the real extension, `.DirIcon` doesn't have one
+            let path = if path.extension().is_none() {
+                let typed = path.with_extension(ext);
+                fs::copy(&path, &typed).await?;
+                typed
+            } else {
+                path
+            };
+            icons.insert(size, path);
+        }
+    }
+
+    let mut installed = vec![];
+
+    for (size, path) in icons {
+        let Some(ext) = path.extension().and_then(|ext| ext.to_str()) else {
+            continue;
+        };
+        let dir = config.hicolor_dir().join(size).join("apps");
+        fs::create_dir_all(&dir).await?;
+
+        let destination = dir.join(format!("{}.{ext}", icon_name(executable)));
+        fs::copy(&path, &destination).await?;
+        installed.push(destination);
+    }
+
+    if !installed.is_empty() {
+        refresh_icon_cache(config);
+    }
+
+    Ok(installed)
+}
+
+pub async fn remove_icons(config: &Config, executable: &str) -> Result<()> {
+    let hicolor = config.hicolor_dir();
+
+    if !fs::try_exists(&hicolor).await? {
+        return Ok(());
+    }
+
+    let name = icon_name(executable);
+    let mut removed = false;
+
+    let mut sizes = fs::read_dir(&hicolor).await?;
+    while let Some(size) = sizes.next_entry().await? {
+        // Skip icon-theme.cache and friends
+        if !size.file_type().await?.is_dir() {
+            continue;
+        }
+
+        for ext in ICON_EXTENSIONS {
+            let path = size.path().join(format!("apps/{name}.{ext}"));
+            if fs::try_exists(&path).await? {
+                fs::remove_file(path).await?;
+                removed = true;
+            }
+        }
+    }
+
+    if removed {
+        refresh_icon_cache(config);
+    }
+
+    Ok(())
+}
+
+fn refresh_icon_cache(config: &Config) {
+    if let Some(hicolor) = config.hicolor_dir().to_str() {
+        run_if_available(
+            "gtk-update-icon-cache",
+            &["--quiet", "--ignore-theme-index", "--force", hicolor],
+        );
+    }
+}
+
+fn mime_package_prefix(executable: &str) -> String {
+    format!("zap-rs-{executable}-")
+}
+
+/// Installs the shared-mime-info packages shipped in `usr/share/mime/packages`.
+pub async fn install_mime_packages(
+    config: &Config,
+    squashfs: &Path,
+    executable: &str,
+) -> Result<Vec<PathBuf>> {
+    let packages = squashfs.join("usr/share/mime/packages");
+    let mut installed = vec![];
+
+    if !fs::try_exists(&packages).await? {
+        return Ok(installed);
+    }
+
+    let destination_dir = config.mime_dir().join("packages");
+    fs::create_dir_all(&destination_dir).await?;
+
+    let mut entries = fs::read_dir(&packages).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        if entry.path().extension() != Some("xml".as_ref()) {
+            continue;
+        }
+
+        let destination = destination_dir.join(format!(
+            "{}{}",
+            mime_package_prefix(executable),
+            entry.file_name().to_string_lossy()
+        ));
+        fs::copy(entry.path(), &destination).await?;
+        installed.push(destination);
+    }
+
+    if !installed.is_empty() {
+        refresh_mime_database(config);
+    }
+
+    Ok(installed)
+}
+
+/// Only the recorded packages go, another app's name may well start with this one's.
+pub async fn remove_mime_packages(config: &Config, packages: &[PathBuf]) -> Result<()> {
+    let mut removed = false;
+
+    for path in packages {
+        if fs::try_exists(path).await? {
+            fs::remove_file(path).await?;
+            removed = true;
+        }
+    }
+
+    if removed {
+        refresh_mime_database(config);
+    }
+
+    Ok(())
+}
+
+fn refresh_mime_database(config: &Config) {
+    if let Some(mime_dir) = config.mime_dir().to_str() {
+        run_if_available("update-mime-database", &[mime_dir]);
+    }
+}
+
+/// Lets the desktop pick up `MimeType=`, including `x-scheme-handler/*` entries.
+pub fn refresh_desktop_database(config: &Config) {
+    if let Some(applications_dir) = config.applications_dir().to_str() {
+        run_if_available("update-desktop-database", &["--quiet", applications_dir]);
+    }
+}
+
+/// Other tools write `mimeapps.list` too, one we can't make sense of is left alone rather
+/// than failing the whole (un)integration.
+async fn read_mimeapps(config: &Config) -> Result<Option<DesktopEntry>> {
+    let mimeapps_list = config.mimeapps_list();
+
+    if !fs::try_exists(&mimeapps_list).await? {
+        return DesktopEntry::parse("").map(Some);
+    }
+
+    match DesktopEntry::parse(&fs::read_to_string(&mimeapps_list).await?) {
+        Ok(mimeapps) => Ok(Some(mimeapps)),
+        Err(e) => {
+            eprintln!("Skipping {}: {e}", mimeapps_list.display());
+            Ok(None)
+        }
+    }
+}
+
+async fn write_mimeapps(config: &Config, mimeapps: &DesktopEntry) -> Result<()> {
+    let mimeapps_list = config.mimeapps_list();
+
+    if let Some(parent) = mimeapps_list.parent() {
+        fs::create_dir_all(parent).await?;
+    }
+
+    fs::write(mimeapps_list, mimeapps.to_string()).await?;
+
+    Ok(())
+}
+
+fn without_desktop_id(list: &str, desktop_id: &str) -> Vec<String> {
+    list.split(';')
+        .filter(|id| !id.is_empty() && *id != desktop_id)
+        .map(str::to_string)
+        .collect()
+}
+
+/// Makes the app the default handler for the given MIME types in `mimeapps.list`.
+pub async fn set_default_handler(
+    config: &Config,
+    desktop_id: &str,
+    mime_types: &[String],
+) -> Result<()> {
+    if mime_types.is_empty() {
+        return Ok(());
+    }
+
+    let Some(mut mimeapps) = read_mimeapps(config).await? else {
+        return Ok(());
+    };
+    let defaults = mimeapps.group_or_insert("Default Applications");
+
+    for mime_type in mime_types {
+        let mut ids = vec![desktop_id.to_string()];
+        ids.extend(without_desktop_id(
+            &defaults.get(mime_type).unwrap_or_default(),
+            desktop_id,
+        ));
+        defaults.set(mime_type, &format!("{};", ids.join(";")));
+    }
+
+    write_mimeapps(config, &mimeapps).await
+}
+
+/// Drops the app from every association in `mimeapps.list`.
+pub async fn remove_default_handler(config: &Config, desktop_id: &str) -> Result<()> {
+    if !fs::try_exists(config.mimeapps_list()).await? {
+        return Ok(());
+    }
+
+    let Some(mut mimeapps) = read_mimeapps(config).await? else {
+        return Ok(());
+    };
+    let mut changed = false;
+
+    for group in mimeapps.groups_mut() {
+        for (mime_type, list) in group.entries() {
+            if !list.split(';').any(|id| id == desktop_id) {
+                continue;
+            }
+
+            let ids = without_desktop_id(&list, desktop_id);
+            if ids.is_empty() {
+                group.remove(&mime_type);
+            } else {
+                group.set(&mime_type, &format!("{};", ids.join(";")));
+            }
+            changed = true;
+        }
+    }
+
+    if changed {
+        write_mimeapps(config, &mimeapps).await?;
+    }
+
+    Ok(())
+}
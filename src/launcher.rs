@@ -0,0 +1,91 @@
+use std::path::{Path, PathBuf};
+use tokio::fs;
+
+use crate::{Config, ElfInfo, Result};
+
+pub fn launcher_path(config: &Config, executable: &str) -> PathBuf {
+    config.launchers_dir().join(executable)
+}
+
+/// Quotes a value for a POSIX shell script.
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+/// Writes a shell script that sets `env` and hands over to `command`, passing the
+/// arguments it was called with after the ones in `command`.
+pub async fn write_launcher(
+    config: &Config,
+    executable: &str,
+    command: &[String],
+    env: &[(&str, &str)],
+) -> Result<PathBuf> {
+    fs::create_dir_all(config.launchers_dir()).await?;
+
+    let mut script = String::from("#!/bin/sh\n");
+    for (key, value) in env {
+        script.push_str(&format!("export {key}={}\n", shell_quote(value)));
+    }
+    script.push_str("exec");
+    for arg in command {
+        script.push_str(&format!(" {}", shell_quote(arg)));
+    }
+    script.push_str(" \"$@\"\n");
+
+    let path = launcher_path(config, executable);
+    fs::write(&path, script).await?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).await?;
+    }
+
+    Ok(path)
+}
+
+pub async fn remove_launcher(config: &Config, executable: &str) -> Result<()> {
+    let path = launcher_path(config, executable);
+
+    if fs::try_exists(&path).await? {
+        fs::remove_file(path).await?;
+    }
+
+    Ok(())
+}
+
+pub fn in_path(program: &str) -> bool {
+    std::env::var_os("PATH")
+        .is_some_and(|paths| std::env::split_paths(&paths).any(|dir| dir.join(program).is_file()))
+}
+
+fn libfuse2_available() -> bool {
+    if let Ok(output) = std::process::Command::new("ldconfig").arg("-p").output()
+        && output.status.success()
+    {
+        return String::from_utf8_lossy(&output.stdout).contains("libfuse.so.2");
+    }
+
+    [
+        "/usr/lib",
+        "/usr/lib64",
+        "/lib",
+        "/lib64",
+        "/usr/lib/x86_64-linux-gnu",
+        "/usr/lib/aarch64-linux-gnu",
+    ]
+    .iter()
+    .any(|dir| Path::new(dir).join("libfuse.so.2").exists())
+}
+
+/// Whether the AppImage at `path` can mount itself, static runtimes don't need libfuse2.
+pub async fn fuse_available(path: &Path) -> bool {
+    if !Path::new("/dev/fuse").exists() || !(in_path("fusermount") || in_path("fusermount3")) {
+        return false;
+    }
+
+    match ElfInfo::read(path).await {
+        Ok(elf) if !elf.interpreter => true,
+        _ => libfuse2_available(),
+    }
+}
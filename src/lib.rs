@@ -1,9 +1,22 @@
 mod appimage;
 mod args;
+mod catalog;
+mod config;
+mod desktop;
 mod downloader;
+mod elf;
 mod index;
+mod info;
+mod inspect;
+mod integration;
+mod launcher;
+mod lock;
 mod manager;
+mod metainfo;
 mod paths;
+mod registry;
+mod sandbox;
+mod sqlite;
 mod symlink;
 mod tui;
 mod error;
@@ -11,10 +24,23 @@ mod github;
 
 pub use crate::appimage::*;
 pub use crate::args::*;
+pub use crate::catalog::*;
+pub use crate::config::*;
+pub use crate::desktop::*;
 pub use crate::downloader::*;
+pub use crate::elf::*;
 pub use crate::index::*;
+pub use crate::info::*;
+pub use crate::inspect::*;
+pub use crate::integration::*;
+pub use crate::launcher::*;
+pub use crate::lock::*;
 pub use crate::manager::*;
+pub use crate::metainfo::*;
 pub use crate::paths::*;
+pub use crate::registry::*;
+pub use crate::sandbox::*;
+pub use crate::sqlite::*;
 pub use crate::symlink::*;
 pub use crate::tui::*;
 pub use crate::error::*;
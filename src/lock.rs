@@ -0,0 +1,39 @@
+use std::{
+    fs::{File, OpenOptions, TryLockError},
+    path::{Path, PathBuf},
+};
+
+use crate::{Error, Result};
+
+/// An advisory lock on the store, held while a command changes anything in it.
+/// It's released when dropped, or when the process dies.
+#[derive(Debug)]
+pub struct StoreLock {
+    _file: File,
+}
+
+impl StoreLock {
+    pub fn acquire(path: &Path, wait: bool) -> Result<Self> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(path)?;
+
+        match file.try_lock() {
+            Ok(()) => {}
+            Err(TryLockError::WouldBlock) if wait => {
+                println!("Waiting for another zap-rs to finish...");
+                file.lock()?;
+            }
+            Err(TryLockError::WouldBlock) => return Err(Error::StoreLocked(PathBuf::from(path))),
+            Err(TryLockError::Error(e)) => return Err(e.into()),
+        }
+
+        Ok(Self { _file: file })
+    }
+}
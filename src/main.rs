@@ -1,29 +1,166 @@
 use clap::Parser;
 use colored::Colorize;
 
-use zap_rs::{AppImage, Cli, Command, PackageManager, Result};
+use zap_rs::{
+    AliasCommand, AppImage, Cli, Command, Config, EnvCommand, IndexCommand, PackageManager,
+    PortableCommand, RegistryCommand, RegistrySource, Result, Scope,
+};
 
 async fn run() -> Result<()> {
     let args = Cli::parse();
-    let pm = PackageManager::new();
+    let config = Config::load().await?;
+    let scoped = config.clone().with_scope(args.scope());
+    let modifies = args.command.modifies();
+
+    // Held until the command is done, opening the index may already write to the store
+    let _lock = if modifies {
+        PackageManager::check_access(&scoped)?;
+        Some(PackageManager::lock(&scoped, args.wait)?)
+    } else {
+        None
+    };
+    let pm = PackageManager::new(scoped, modifies).await?;
 
     match args.command {
         Command::Install(args) => {
-            let mut appimage = AppImage::new(&args);
+            if args.from.is_some() {
+                let mut appimage = AppImage::new(&args);
 
-            pm.install(&mut appimage, &args.appname).await?;
+                pm.install(&mut appimage, &args).await?;
+            } else {
+                let recipe = pm.resolve_recipe(&args.appname).await?;
+                let mut appimage = AppImage::from_recipe(&recipe, &args);
+
+                pm.install_checked(&mut appimage, &args, &recipe.checksum)
+                    .await?;
+            }
         }
         Command::Update(args) => {
             let mut appimage = pm.index.get(&args.appname).await?;
 
-            pm.update(&mut appimage).await?;
+            pm.update(&mut appimage, &args.appname).await?;
         }
         Command::Remove(args) => {
-            pm.remove(&args.appname).await?;
+            pm.remove(&args.appname, args.purge).await?;
+        }
+        Command::Integrate(args) => {
+            let appnames = match args.appname {
+                Some(appname) => vec![appname],
+                None => pm.index.list().await?,
+            };
+
+            for appname in appnames {
+                pm.integrate(&appname, args.default_handler, args.force)
+                    .await?;
+            }
+        }
+        Command::Unintegrate(args) => {
+            let appnames = match args.appname {
+                Some(appname) => vec![appname],
+                None => pm.index.list().await?,
+            };
+
+            for appname in appnames {
+                pm.unintegrate(&appname).await?;
+            }
         }
+        Command::Mode(args) => {
+            pm.set_run_mode(&args.appname, args.mode).await?;
+        }
+        Command::Env(command) => match command {
+            EnvCommand::Set(args) => {
+                pm.set_env(&args.appname, &args.vars).await?;
+            }
+            EnvCommand::Unset(args) => {
+                pm.unset_env(&args.appname, &args.keys).await?;
+            }
+        },
+        Command::Args(args) => {
+            pm.set_args(&args.appname, args.args).await?;
+        }
+        Command::Alias(command) => match command {
+            AliasCommand::Add(args) => {
+                pm.add_alias(&args.appname, &args.alias, args.force).await?;
+            }
+            AliasCommand::Remove(args) => {
+                pm.remove_alias(&args.appname, &args.alias).await?;
+            }
+        },
+        Command::Portable(command) => match command {
+            PortableCommand::Enable(args) => {
+                let (home, config) = args.dirs();
+                pm.set_portable(&args.appname, home, config, true).await?;
+            }
+            PortableCommand::Disable(args) => {
+                let (home, config) = args.dirs();
+                pm.set_portable(&args.appname, home, config, false).await?;
+            }
+            PortableCommand::Backup(args) => {
+                pm.backup_portable(&args.appname, &args.archive).await?;
+            }
+            PortableCommand::Restore(args) => {
+                pm.restore_portable(&args.appname, &args.archive).await?;
+            }
+        },
+        Command::Sandbox(args) => {
+            pm.sandbox(&args).await?;
+        }
+        Command::Owns(args) => match pm.owner_of(&args.path).await? {
+            Some(appname) => println!("{} belongs to {appname}", args.path.display()),
+            None => println!("{} doesn't belong to any AppImage", args.path.display()),
+        },
         Command::List => {
-            pm.list().await?;
+            for scope in [Scope::System, Scope::User] {
+                PackageManager::new(config.clone().with_scope(scope), false)
+                    .await?
+                    .list()
+                    .await?;
+            }
+        }
+        Command::History => {
+            pm.history().await?;
+        }
+        Command::Info(args) => {
+            pm.info(&args.appname, args.json).await?;
+        }
+        Command::Inspect(args) => {
+            pm.inspect(&args.target, args.json).await?;
+        }
+        Command::Search(args) => {
+            pm.search(&args.term).await?;
         }
+        Command::Registry(command) => match command {
+            RegistryCommand::Add(args) => {
+                pm.registry
+                    .add_source(RegistrySource {
+                        name: args.name,
+                        url: args.url,
+                        token: args.token,
+                    })
+                    .await?;
+            }
+            RegistryCommand::Remove(args) => {
+                pm.registry.remove_source(&args.name).await?;
+            }
+            RegistryCommand::List => {
+                for source in pm.registry.sources().await? {
+                    println!("- {}: {}", source.name, source.url);
+                }
+            }
+            RegistryCommand::Refresh => {
+                pm.registry.refresh().await?;
+                pm.catalog.refresh().await?;
+                println!("Refreshed the AppImage catalog");
+            }
+        },
+        Command::Index(command) => match command {
+            IndexCommand::Migrate(args) => {
+                pm.migrate_index(args.dry_run).await?;
+            }
+            IndexCommand::Import => {
+                pm.import_index().await?;
+            }
+        },
     };
 
     Ok(())
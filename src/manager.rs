@@ -1,31 +1,115 @@
+use chrono::{SecondsFormat, Utc};
 use std::{
     io::{self, Write},
-    path::PathBuf,
+    path::{Path, PathBuf},
+    process::Command,
+    sync::Arc,
 };
-use tokio::fs;
+use tokio::{fs, io::AsyncWriteExt};
 
 use crate::{
-    AppImage, Downloader, Error, Index, Result, SymlinkManager, desktops_dir,
-    get_github_release_url, icons_dir, index_dir,
+    AppImage, AppInfo, Catalog, ChecksumPolicy, Config, Downloader, Error, INDEX_SCHEMA_VERSION,
+    IndexBackend, IndexStore, Inspection, InstallArgs, IntegrateMode, JsonIndex, PORTABLE_DIRS,
+    Recipe, Registry, Result, RunMode, SandboxArgs, SandboxBackend, SandboxProfile, Scope,
+    StoreLock, SymlinkManager, fuse_available, get_github_release_url, in_path, is_writable,
+    make_temp_dir, open_index, validate_device, verify_arch,
 };
 
-#[derive(Debug, Default)]
+fn confirm(prompt: &str, default: bool) -> Result<bool> {
+    print!("{prompt}");
+    io::stdout().flush()?;
+
+    let mut input = String::new();
+    io::stdin().read_line(&mut input)?;
+
+    Ok(match input.to_lowercase().trim() {
+        "y" | "yes" => true,
+        "n" | "no" => false,
+        _ => default,
+    })
+}
+
+/// Aliases end up as file names in the bin directory, next to the executable.
+fn validate_alias(alias: &str, appimage: &AppImage) -> Result<()> {
+    if alias.is_empty()
+        || alias == "."
+        || alias == ".."
+        || alias.contains('/')
+        || alias == appimage.executable
+    {
+        return Err(Error::InvalidAlias(alias.to_string()));
+    }
+
+    Ok(())
+}
+
+fn is_env_key(key: &str) -> bool {
+    key.chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+#[derive(Debug)]
 pub struct PackageManager {
+    pub config: Arc<Config>,
     pub downloader: Downloader,
-    pub index: Index,
+    pub index: Box<dyn IndexStore>,
     pub symlink_manager: SymlinkManager,
+    pub registry: Registry,
+    pub catalog: Catalog,
 }
 
 impl PackageManager {
-    pub fn new() -> Self {
-        Self {
-            downloader: Downloader::new(),
-            index: Index::new(),
-            symlink_manager: SymlinkManager::new(),
+    /// With `modifies`, the index may be created, so the store has to be locked first.
+    pub async fn new(config: Config, modifies: bool) -> Result<Self> {
+        let config = Arc::new(config);
+        let client = config.http_client()?;
+
+        Ok(Self {
+            downloader: Downloader::new(config.clone(), client.clone()),
+            index: open_index(config.clone(), modifies).await?,
+            symlink_manager: SymlinkManager::new(config.clone()),
+            registry: Registry::new(config.clone(), client.clone()),
+            catalog: Catalog::new(config.clone(), client),
+            config,
+        })
+    }
+    /// Installing for all users needs write access to the system directories, usually root.
+    pub fn check_access(config: &Config) -> Result<()> {
+        if config.scope == Scope::User {
+            return Ok(());
+        }
+
+        for dir in [
+            config.store_dir(),
+            config.bin_dir(),
+            config.applications_dir(),
+        ] {
+            if !is_writable(&dir) {
+                return Err(Error::NoWriteAccess(dir));
+            }
         }
+
+        Ok(())
+    }
+    /// Keeps other zap-rs processes out of the store until the lock is dropped.
+    pub fn lock(config: &Config, wait: bool) -> Result<StoreLock> {
+        StoreLock::acquire(&config.lock_file(), wait)
+    }
+    pub async fn install(&self, appimage: &mut AppImage, options: &InstallArgs) -> Result<()> {
+        self.install_checked(appimage, options, &ChecksumPolicy::Skip)
+            .await
     }
-    pub async fn install(&self, appimage: &mut AppImage, appname: &str) -> Result<()> {
-        if self.index.exists(&appimage.executable)? {
+    pub async fn install_checked(
+        &self,
+        appimage: &mut AppImage,
+        options: &InstallArgs,
+        checksum: &ChecksumPolicy,
+    ) -> Result<()> {
+        let appname = &options.appname;
+
+        if self.index.exists(&appimage.executable).await? {
             println!("{} is already installed.", appimage.executable);
             return Ok(());
         }
@@ -34,86 +118,774 @@ impl PackageManager {
             .downloader
             .prepare_path(&appimage.source.meta.url, &appimage.executable)?;
 
-        if appimage.source.identifier != "git.github" {
-            self.downloader
-                .download_with_progress(&appimage.source.meta.url, &appimage.file_path)
-                .await?;
-        } else {
-            self.downloader
-                .download_with_progress(
-                    &get_github_release_url(appimage).await?,
-                    &appimage.file_path,
-                )
-                .await?;
+        for alias in &options.aliases {
+            validate_alias(alias, appimage)?;
         }
+        appimage.aliases = options.aliases.clone();
 
-        self.index.add(appimage, appname).await?;
-        self.symlink_manager.create(appimage).await?;
+        // Find out before downloading anything
+        let checked = self.symlink_manager.check(appimage, options.force).await;
+        self.with_owner(checked).await?;
+
+        let sha256 = self.fetch(appimage, checksum).await?;
+        appimage.sha256 = Some(sha256);
+        appimage.installed_at = Some(Utc::now());
+        appimage.refresh_metadata().await;
 
-        print!("Do you want to integrate this appimage? (y/N) ");
-        io::stdout().flush()?;
+        appimage.run_mode = match options.mode {
+            Some(mode) => mode,
+            None if !fuse_available(&appimage.file_path).await => {
+                println!("FUSE isn't available here, so {appname} can't mount itself.");
+                if confirm("Extract it so it runs without FUSE? (Y/n) ", true)? {
+                    RunMode::Extract
+                } else {
+                    RunMode::Fuse
+                }
+            }
+            None => RunMode::Fuse,
+        };
+        // Data kept from an earlier install of the app is picked up again
+        appimage.portable = appimage.find_portable(&self.config).await?;
+        if appimage.portable.is_enabled() {
+            println!(
+                "Using the portable directories in {}.",
+                appimage.portable_path(&self.config).display()
+            );
+        }
+        appimage.prepare_run_mode(&self.config).await?;
+
+        self.create_symlink(appimage, options.force).await?;
+        self.index.add(appimage, appname).await?;
+        self.record_history("install", appname, appimage.version.as_deref())
+            .await?;
 
-        let mut input = String::new();
-        io::stdin().read_line(&mut input)?;
+        let integrate = match self.config.integration.integrate {
+            IntegrateMode::Ask => confirm("Do you want to integrate this appimage? (y/N) ", false)?,
+            IntegrateMode::Always => true,
+            IntegrateMode::Never => false,
+        };
+        if integrate {
+            let default_handler =
+                options.default_handler || self.config.integration.default_handler;
 
-        if input.to_lowercase().trim() == "y" || input.to_lowercase().trim() == "yes" {
-            appimage.integrate_desktop().await?;
+            let checked = appimage
+                .check_desktop_files(&self.config, options.force)
+                .await;
+            self.with_owner(checked).await?;
+            appimage.integration = Some(
+                appimage
+                    .integrate_desktop(&self.config, default_handler)
+                    .await?,
+            );
+            self.index.add(appimage, appname).await?;
         }
 
         Ok(())
     }
-    pub async fn remove(&self, appname: &str) -> Result<()> {
+    pub async fn remove(&self, appname: &str, purge: bool) -> Result<()> {
         let appimage = self.index.get(appname).await?;
 
         fs::remove_file(&appimage.file_path).await?;
-        self.symlink_manager.remove(&appimage.executable).await?;
+        appimage.cleanup_run_mode(&self.config).await?;
+        if purge {
+            appimage.purge_portable(&self.config).await?;
+        } else if appimage.find_portable(&self.config).await?.is_enabled() {
+            println!(
+                "Kept the portable directories in {}, remove with --purge to delete them.",
+                appimage.portable_path(&self.config).display()
+            );
+        }
+        self.symlink_manager.remove(&appimage).await?;
         self.index.remove(appname).await?;
 
-        if fs::try_exists(desktops_dir()?.join(format!("{}.desktop", appimage.executable))).await? {
-            fs::remove_file(desktops_dir()?.join(format!("{}.desktop", appimage.executable)))
-                .await?;
+        appimage.unintegrate_desktop(&self.config).await?;
+        self.record_history("remove", appname, appimage.version.as_deref())
+            .await?;
+
+        Ok(())
+    }
+    /// Appends a line to the history in the state directory.
+    async fn record_history(
+        &self,
+        action: &str,
+        appname: &str,
+        version: Option<&str>,
+    ) -> Result<()> {
+        let history_file = self.config.history_file();
+        if let Some(parent) = history_file.parent() {
+            fs::create_dir_all(parent).await?;
         }
-        if fs::try_exists(PathBuf::from(std::env::var("HOME")?).join(format!(
-            ".local/share/applications/{}.desktop",
-            appimage.executable
-        )))
-        .await?
-        {
-            fs::remove_file(PathBuf::from(std::env::var("HOME")?).join(format!(
-                ".local/share/applications/{}.desktop",
-                appimage.executable
-            )))
+
+        let mut line = format!(
+            "{} {action} {appname}",
+            Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true)
+        );
+        if let Some(version) = version {
+            line.push_str(&format!(" {version}"));
+        }
+        line.push('\n');
+
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(history_file)
             .await?;
+        file.write_all(line.as_bytes()).await?;
+
+        Ok(())
+    }
+    pub async fn history(&self) -> Result<()> {
+        match fs::read_to_string(self.config.history_file()).await {
+            Ok(history) => print!("{history}"),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => println!("Nothing installed yet."),
+            Err(e) => return Err(e.into()),
         }
-        if fs::try_exists(icons_dir()?.join(format!("{}.png", appimage.executable))).await? {
-            fs::remove_file(icons_dir()?.join(format!("{}.png", appimage.executable))).await?;
+
+        Ok(())
+    }
+    async fn create_symlink(&self, appimage: &mut AppImage, force: bool) -> Result<()> {
+        let created = self.symlink_manager.create(appimage, force).await;
+
+        for symlink_path in self.with_owner(created).await? {
+            if !appimage.symlinks.contains(&symlink_path) {
+                appimage.symlinks.push(symlink_path);
+            }
         }
 
         Ok(())
     }
+    pub async fn add_alias(&self, appname: &str, alias: &str, force: bool) -> Result<()> {
+        let mut appimage = self.index.get(appname).await?;
+
+        if appimage.commands().any(|name| name == alias) {
+            println!("{appname} can already be started as {alias}.");
+            return Ok(());
+        }
+        validate_alias(alias, &appimage)?;
+
+        appimage.aliases.push(alias.to_string());
+        self.create_symlink(&mut appimage, force).await?;
+        self.index.add(&appimage, appname).await?;
+
+        Ok(())
+    }
+    pub async fn remove_alias(&self, appname: &str, alias: &str) -> Result<()> {
+        let mut appimage = self.index.get(appname).await?;
+
+        if !appimage.aliases.iter().any(|name| name == alias) {
+            println!("{alias} isn't an alias of {appname}.");
+            return Ok(());
+        }
+
+        let symlink_path = self.symlink_manager.symlink_path(alias);
+        self.symlink_manager
+            .remove_link(&symlink_path, &appimage)
+            .await?;
+
+        appimage.aliases.retain(|name| name != alias);
+        appimage.symlinks.retain(|path| *path != symlink_path);
+        self.index.add(&appimage, appname).await?;
+
+        Ok(())
+    }
+    /// Names the app a colliding file belongs to, if it's one of ours.
+    async fn with_owner<T>(&self, result: Result<T>) -> Result<T> {
+        match result {
+            Err(Error::FileCollision { path, owner: None }) => Err(Error::FileCollision {
+                owner: self.owner_of(&path).await?,
+                path,
+            }),
+            result => result,
+        }
+    }
+    pub async fn owner_of(&self, path: &Path) -> Result<Option<String>> {
+        let path = std::path::absolute(path)?;
+
+        if let Some(appname) = self.index.owner_of(&path).await? {
+            return Ok(Some(appname));
+        }
+
+        // Symlinks made before they were recorded aren't in the index
+        for (appname, appimage) in self.index.entries().await? {
+            let Ok(appimage) = appimage else {
+                continue;
+            };
+
+            if appimage.symlinks.is_empty()
+                && self.symlink_manager.links(&appimage).await?.contains(&path)
+            {
+                return Ok(Some(appname));
+            }
+        }
+
+        Ok(None)
+    }
     pub async fn list(&self) -> Result<()> {
-        let mut appimages = fs::read_dir(index_dir()?).await?;
+        for (appname, appimage) in self.index.entries().await? {
+            let Ok(appimage) = appimage else {
+                println!("- {appname} [{}]", self.config.scope);
+                continue;
+            };
 
-        while let Some(appimage) = appimages.next_entry().await? {
-            if let Some(stem) = appimage.path().file_stem().and_then(|s| s.to_str()) {
-                println!("- {stem}");
+            let mut line = format!("- {appname}");
+            if let Some(version) = &appimage.version {
+                line.push_str(&format!(" {version}"));
+            }
+            line.push_str(&format!(" [{}]", self.config.scope));
+            if !appimage.aliases.is_empty() {
+                line.push_str(&format!(" (also {})", appimage.aliases.join(", ")));
             }
+            if let Some(summary) = appimage
+                .metainfo
+                .as_ref()
+                .and_then(|metainfo| metainfo.summary.as_ref())
+            {
+                line.push_str(&format!(": {summary}"));
+            }
+
+            println!("{line}");
         }
 
         Ok(())
     }
-    pub async fn update(&self, appimage: &mut AppImage) -> Result<()> {
-        if appimage.source.identifier != "git.github".to_string() {
-            return Err(Error::CantUpdatePkg);
+    pub async fn info(&self, appname: &str, json: bool) -> Result<()> {
+        let appimage = self.index.get(appname).await?;
+        let info = AppInfo::new(appname, &appimage, &self.config, &self.symlink_manager).await?;
+
+        if json {
+            println!("{}", serde_json::to_string_pretty(&info)?);
+        } else {
+            print!("{info}");
         }
 
+        Ok(())
+    }
+    /// Looks at a file or URL without installing it, downloads go to the cache.
+    pub async fn inspect(&self, target: &str, json: bool) -> Result<()> {
+        let is_url = target.starts_with("http://") || target.starts_with("https://");
+
+        // Runs without the store lock, so every run downloads to a directory of its own
+        let temp_dir = if is_url {
+            let inspect_dir = self.config.cache_dir().join("inspect");
+            fs::create_dir_all(&inspect_dir).await?;
+            Some(make_temp_dir(&inspect_dir)?)
+        } else {
+            None
+        };
+
+        let path = if let Some(temp_dir) = &temp_dir {
+            let path = temp_dir.join(
+                target
+                    .rsplit('/')
+                    .next()
+                    .filter(|name| !name.is_empty())
+                    .unwrap_or("download.AppImage"),
+            );
+            if let Err(e) = self.downloader.download_to(target, &path).await {
+                fs::remove_dir_all(temp_dir).await?;
+                return Err(e);
+            }
+            path
+        } else {
+            let path = PathBuf::from(target.strip_prefix("file://").unwrap_or(target));
+            if !fs::try_exists(&path).await? {
+                return Err(Error::FileNotFound(path));
+            }
+            path
+        };
+
+        let inspection = Inspection::new(&path).await;
+
+        if let Some(temp_dir) = temp_dir {
+            fs::remove_dir_all(temp_dir).await?;
+        }
+
+        let inspection = inspection?;
+        if json {
+            println!("{}", serde_json::to_string_pretty(&inspection)?);
+        } else {
+            print!("{inspection}");
+        }
+
+        Ok(())
+    }
+    pub async fn set_run_mode(&self, appname: &str, run_mode: RunMode) -> Result<()> {
+        let mut appimage = self.index.get(appname).await?;
+
+        appimage.run_mode = run_mode;
+        self.apply_launch_settings(&mut appimage, appname).await
+    }
+    pub async fn set_portable(
+        &self,
+        appname: &str,
+        home: bool,
+        config: bool,
+        enabled: bool,
+    ) -> Result<()> {
+        // Everyone would share one home, which only root could write to
+        if enabled && self.config.scope == Scope::System {
+            return Err(Error::NotSystemWide("Portable directories".to_string()));
+        }
+
+        let mut appimage = self.index.get(appname).await?;
+
+        if home {
+            appimage.portable.home = enabled;
+        }
+        if config {
+            appimage.portable.config = enabled;
+        }
+
+        self.apply_launch_settings(&mut appimage, appname).await?;
+
+        if !enabled && fs::try_exists(appimage.portable_path(&self.config)).await? {
+            println!(
+                "Kept the portable directories in {}.",
+                appimage.portable_path(&self.config).display()
+            );
+        }
+
+        Ok(())
+    }
+    /// Saves the portable directories, which aren't next to the AppImage to be picked up with it.
+    pub async fn backup_portable(&self, appname: &str, archive: &Path) -> Result<()> {
+        let appimage = self.index.get(appname).await?;
+        let portable_path = appimage.portable_path(&self.config);
+
+        let mut dirs = vec![];
+        for dir in PORTABLE_DIRS {
+            if fs::try_exists(portable_path.join(dir)).await? {
+                dirs.push(dir);
+            }
+        }
+        if dirs.is_empty() {
+            return Err(Error::NoPortableDirs(appname.to_string()));
+        }
+        if !in_path("tar") {
+            return Err(Error::TarUnavailable);
+        }
+
+        let status = Command::new("tar")
+            .arg("-czf")
+            .arg(archive)
+            .arg("-C")
+            .arg(&portable_path)
+            .args(dirs)
+            .status()?;
+        if !status.success() {
+            return Err(Error::PortableBackupFailed(archive.to_path_buf()));
+        }
+
+        println!(
+            "Saved the portable directories of {appname} to {}.",
+            archive.display()
+        );
+
+        Ok(())
+    }
+    pub async fn restore_portable(&self, appname: &str, archive: &Path) -> Result<()> {
+        if self.config.scope == Scope::System {
+            return Err(Error::NotSystemWide("Portable directories".to_string()));
+        }
+
+        let mut appimage = self.index.get(appname).await?;
+
+        if !fs::try_exists(archive).await? {
+            return Err(Error::FileNotFound(archive.to_path_buf()));
+        }
+        if !in_path("tar") {
+            return Err(Error::TarUnavailable);
+        }
+
+        // Unpacked aside first, so a broken archive leaves the current directories alone
+        fs::create_dir_all(self.config.portable_dir()).await?;
+        let temp_dir = make_temp_dir(&self.config.portable_dir())?;
+
+        let status = Command::new("tar")
+            .arg("-xf")
+            .arg(archive)
+            .arg("-C")
+            .arg(&temp_dir)
+            .status()?;
+
+        let mut names = vec![];
+        let mut entries = fs::read_dir(&temp_dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            names.push(entry.file_name());
+        }
+        let is_backup = !names.is_empty()
+            && names
+                .iter()
+                .all(|name| PORTABLE_DIRS.iter().any(|dir| name == dir));
+
+        if !status.success() || !is_backup {
+            fs::remove_dir_all(&temp_dir).await?;
+            return Err(Error::InvalidPortableBackup(archive.to_path_buf()));
+        }
+
+        let portable_path = appimage.portable_path(&self.config);
+        if fs::try_exists(&portable_path).await? {
+            fs::remove_dir_all(&portable_path).await?;
+        }
+        fs::rename(&temp_dir, &portable_path).await?;
+
+        appimage.portable = appimage.find_portable(&self.config).await?;
+        self.apply_launch_settings(&mut appimage, appname).await?;
+
+        println!("Restored the portable directories of {appname}.");
+
+        Ok(())
+    }
+    pub async fn sandbox(&self, options: &SandboxArgs) -> Result<()> {
+        let appname = &options.appname;
+        let mut appimage = self.index.get(appname).await?;
+
+        if options.disable {
+            appimage.sandbox = None;
+            return self.apply_launch_settings(&mut appimage, appname).await;
+        }
+        if !options.has_changes() {
+            match &appimage.sandbox {
+                Some(sandbox) => println!("{sandbox}"),
+                None => println!("{appname} isn't sandboxed."),
+            }
+            return Ok(());
+        }
+        // The launcher is shared by everyone, but would hide or bind the home of whoever wrote it
+        if self.config.scope == Scope::System {
+            return Err(Error::NotSystemWide("Sandboxes".to_string()));
+        }
+
+        for device in &options.device {
+            validate_device(device)?;
+        }
+
+        let mut sandbox = match (appimage.sandbox.take(), options.backend) {
+            (Some(sandbox), _) => sandbox,
+            (None, Some(backend)) => SandboxProfile::new(backend),
+            (None, None) => SandboxProfile::new(
+                [SandboxBackend::Bubblewrap, SandboxBackend::Firejail]
+                    .into_iter()
+                    .find(|backend| in_path(backend.program()))
+                    .ok_or_else(|| Error::SandboxUnavailable("bwrap or firejail".to_string()))?,
+            ),
+        };
+
+        if let Some(backend) = options.backend {
+            sandbox.backend = backend;
+        }
+        if options.network || options.no_network {
+            sandbox.network = options.network;
+        }
+        if options.home || options.no_home {
+            sandbox.home = options.home;
+        }
+
+        for path in &options.revoke {
+            let path = std::path::absolute(path)?;
+            sandbox.paths.retain(|allowed| *allowed != path);
+            sandbox.read_only_paths.retain(|allowed| *allowed != path);
+        }
+        for (paths, allowed) in [
+            (&options.allow, &mut sandbox.paths),
+            (&options.allow_read, &mut sandbox.read_only_paths),
+        ] {
+            for path in paths {
+                let path = std::path::absolute(path)?;
+                if !allowed.contains(&path) {
+                    allowed.push(path);
+                }
+            }
+        }
+
+        sandbox
+            .devices
+            .retain(|device| !options.no_device.contains(device));
+        for device in &options.device {
+            if !sandbox.devices.contains(device) {
+                sandbox.devices.push(device.to_string());
+            }
+        }
+
+        appimage.sandbox = Some(sandbox);
+        self.apply_launch_settings(&mut appimage, appname).await
+    }
+    pub async fn set_env(&self, appname: &str, vars: &[String]) -> Result<()> {
+        let mut appimage = self.index.get(appname).await?;
+
+        for var in vars {
+            let (key, value) = var
+                .split_once('=')
+                .filter(|(key, _)| is_env_key(key))
+                .ok_or_else(|| Error::InvalidEnvVar(var.to_string()))?;
+
+            appimage.env.insert(key.to_string(), value.to_string());
+        }
+
+        self.apply_launch_settings(&mut appimage, appname).await
+    }
+    pub async fn unset_env(&self, appname: &str, keys: &[String]) -> Result<()> {
+        let mut appimage = self.index.get(appname).await?;
+
+        for key in keys {
+            if appimage.env.remove(key).is_none() {
+                println!("{key} isn't set for {appname}.");
+            }
+        }
+
+        self.apply_launch_settings(&mut appimage, appname).await
+    }
+    pub async fn set_args(&self, appname: &str, args: Vec<String>) -> Result<()> {
+        let mut appimage = self.index.get(appname).await?;
+
+        appimage.args = args;
+        self.apply_launch_settings(&mut appimage, appname).await
+    }
+    /// Rewrites the launcher and points the command and desktop entries at it.
+    async fn apply_launch_settings(&self, appimage: &mut AppImage, appname: &str) -> Result<()> {
+        appimage.prepare_run_mode(&self.config).await?;
+        self.create_symlink(appimage, false).await?;
+        self.index.add(appimage, appname).await?;
+
+        // Desktop entries point at the command, which may have just moved
+        if appimage.is_integrated(&self.config) {
+            self.reintegrate(appimage, appname).await?;
+        }
+
+        Ok(())
+    }
+    /// Writes the desktop entries, icons and MIME types again for an integrated app.
+    async fn reintegrate(&self, appimage: &mut AppImage, appname: &str) -> Result<()> {
+        let default_handler = appimage
+            .integration
+            .as_ref()
+            .is_some_and(|integration| integration.default_handler);
+
+        let checked = appimage.check_desktop_files(&self.config, false).await;
+        self.with_owner(checked).await?;
+        appimage.unintegrate_desktop(&self.config).await?;
+        appimage.integration = Some(
+            appimage
+                .integrate_desktop(&self.config, default_handler)
+                .await?,
+        );
+
+        self.index.add(appimage, appname).await
+    }
+    pub async fn integrate(&self, appname: &str, default_handler: bool, force: bool) -> Result<()> {
+        let mut appimage = self.index.get(appname).await?;
+
+        let checked = appimage.check_desktop_files(&self.config, force).await;
+        self.with_owner(checked).await?;
+
+        // Start from a clean slate so nothing from a previous integration lingers
+        if appimage.is_integrated(&self.config) {
+            appimage.unintegrate_desktop(&self.config).await?;
+        }
+
+        let default_handler = default_handler || self.config.integration.default_handler;
+        appimage.integration = Some(
+            appimage
+                .integrate_desktop(&self.config, default_handler)
+                .await?,
+        );
+        self.index.add(&appimage, appname).await?;
+
+        Ok(())
+    }
+    pub async fn unintegrate(&self, appname: &str) -> Result<()> {
+        let mut appimage = self.index.get(appname).await?;
+
+        if !appimage.is_integrated(&self.config) {
+            println!("{appname} is not integrated.");
+            return Ok(());
+        }
+
+        appimage.unintegrate_desktop(&self.config).await?;
+        appimage.integration = None;
+        self.index.add(&appimage, appname).await?;
+
+        Ok(())
+    }
+    /// Brings every index entry up to the current layout.
+    pub async fn migrate_index(&self, dry_run: bool) -> Result<()> {
+        let mut migrated = 0;
+        let mut failed = 0;
+
+        // One entry that can't be migrated shouldn't hold back the others
+        for appname in self.index.list().await? {
+            match self.index.migrate(&appname, dry_run).await {
+                Ok(Some(version)) => {
+                    println!("{appname}: schema version {version} -> {INDEX_SCHEMA_VERSION}");
+                    migrated += 1;
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    eprintln!("Skipping {appname}: {e}");
+                    failed += 1;
+                }
+            }
+        }
+        if failed > 0 {
+            eprintln!("{failed} entries couldn't be migrated.");
+        }
+
+        if migrated == 0 {
+            if failed == 0 {
+                println!("The index is up to date.");
+            }
+        } else if dry_run {
+            println!("{migrated} entries would be migrated.");
+        } else {
+            println!(
+                "Migrated {migrated} entries, the old ones are in {}.",
+                self.config.index_backups_dir().display()
+            );
+        }
+
+        Ok(())
+    }
+    /// Copies the JSON index into the database, replacing entries of the same name.
+    pub async fn import_index(&self) -> Result<()> {
+        if self.config.index.backend != IndexBackend::Sqlite {
+            println!("The index is kept as JSON files, set backend = \"sqlite\" in [index] first.");
+            return Ok(());
+        }
+
+        let imported = self
+            .index
+            .import(&JsonIndex::new(self.config.clone()))
+            .await?;
+        println!("Imported {imported} entries from the JSON index.");
+
+        Ok(())
+    }
+    /// Finds a recipe in the registries, falling back to the AppImage catalog.
+    pub async fn resolve_recipe(&self, name: &str) -> Result<Recipe> {
+        match self.registry.get(name).await {
+            Err(Error::RecipeNotFound(_)) => self
+                .catalog
+                .get(name)
+                .await?
+                .and_then(|entry| entry.to_recipe())
+                .ok_or_else(|| Error::RecipeNotFound(name.to_string())),
+            result => result,
+        }
+    }
+    pub async fn search(&self, term: &str) -> Result<()> {
+        for recipe in self.registry.search(term).await? {
+            if recipe.description.is_empty() {
+                println!("- {} ({})", recipe.name, recipe.registry);
+            } else {
+                println!(
+                    "- {} ({}): {}",
+                    recipe.name, recipe.registry, recipe.description
+                );
+            }
+        }
+
+        let entries = match self.catalog.search(term).await {
+            Ok(entries) => entries,
+            Err(e) => {
+                eprintln!("Skipping the AppImage catalog: {e}");
+                return Ok(());
+            }
+        };
+
+        for entry in entries
+            .iter()
+            .filter(|entry| entry.github_slug().is_some())
+            .take(20)
+        {
+            let categories = entry.categories.as_deref().unwrap_or_default().join(", ");
+
+            match &entry.description {
+                Some(description) if !categories.is_empty() => {
+                    println!("- {} (catalog, {categories}): {description}", entry.name)
+                }
+                Some(description) => println!("- {} (catalog): {description}", entry.name),
+                None => println!("- {} (catalog)", entry.name),
+            }
+        }
+
+        Ok(())
+    }
+    /// Gets the AppImage to its `file_path`, which is only replaced once the new file checks out.
+    async fn fetch(&self, appimage: &mut AppImage, checksum: &ChecksumPolicy) -> Result<String> {
+        let (part_path, sha256) = match appimage.source.identifier.as_str() {
+            "git.github" => {
+                self.downloader
+                    .download_part(
+                        &get_github_release_url(appimage, &self.config).await?,
+                        &appimage.file_path,
+                    )
+                    .await?
+            }
+            "local" => {
+                self.downloader
+                    .copy_part(
+                        &PathBuf::from(&appimage.source.meta.url),
+                        &appimage.file_path,
+                    )
+                    .await?
+            }
+            _ => {
+                self.downloader
+                    .download_part(&appimage.source.meta.url, &appimage.file_path)
+                    .await?
+            }
+        };
+
+        if let Err(e) = checksum.verify(&sha256).and(verify_arch(&part_path).await) {
+            fs::remove_file(&part_path).await?;
+            return Err(e);
+        }
         self.downloader
-            .download_with_progress(
-                &get_github_release_url(appimage).await?,
-                &appimage.file_path,
-            )
+            .install_part(&part_path, &appimage.file_path)
             .await?;
 
+        Ok(sha256)
+    }
+    pub async fn update(&self, appimage: &mut AppImage, appname: &str) -> Result<()> {
+        if !appimage.source.is_updatable() {
+            return Err(Error::CantUpdatePkg);
+        }
+
+        // Releases tracking the latest one are resolved without asking, others get to pick
+        // again, unless a tag was asked for when installing
+        if !appimage.source.meta.pinned {
+            appimage.source.meta.tag = None;
+        }
+
+        // A recipe's checksum is for the release it was installed from, not for newer ones
+        let sha256 = self.fetch(appimage, &ChecksumPolicy::Skip).await?;
+        let changed = appimage.sha256.as_deref() != Some(sha256.as_str());
+        appimage.sha256 = Some(sha256);
+
+        if changed {
+            appimage.updated_at = Some(Utc::now());
+            appimage.refresh_metadata().await;
+
+            // An extracted AppDir is a copy of the old version, left to the next update
+            // to retry if this fails
+            if appimage.run_mode == RunMode::Extract {
+                appimage.extract_appdir(&self.config).await?;
+            }
+        }
+
+        // The app itself is updated now, integrating it again can be retried with `integrate`
+        self.index.add(appimage, appname).await?;
+        if !changed {
+            return Ok(());
+        }
+        self.record_history("update", appname, appimage.version.as_deref())
+            .await?;
+
+        // The new version may ship a different desktop entry, icons or MIME types
+        if appimage.is_integrated(&self.config) {
+            self.reintegrate(appimage, appname).await?;
+        }
+
         Ok(())
     }
 }
@@ -0,0 +1,104 @@
+use roxmltree::{Document, Node};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use tokio::fs;
+
+use crate::Result;
+
+/// The human readable bits of an AppStream `metainfo.xml` / `appdata.xml` file.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Metainfo {
+    pub id: Option<String>,
+    pub name: Option<String>,
+    pub summary: Option<String>,
+    pub developer: Option<String>,
+    pub homepage: Option<String>,
+    pub license: Option<String>,
+    pub version: Option<String>,
+}
+
+fn text(node: Node) -> Option<String> {
+    let text = node
+        .descendants()
+        .filter(|n| n.is_text())
+        .filter_map(|n| n.text())
+        .collect::<String>();
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+
+    (!text.is_empty()).then_some(text)
+}
+
+/// Untranslated children only, translations carry an `xml:lang` attribute.
+fn child<'a>(node: Node<'a, 'a>, name: &str) -> Option<Node<'a, 'a>> {
+    node.children().find(|n| {
+        n.has_tag_name(name)
+            && n.attribute(("http://www.w3.org/XML/1998/namespace", "lang"))
+                .is_none()
+    })
+}
+
+fn parse_version(version: &str) -> Vec<u64> {
+    version
+        .split(|c: char| !c.is_ascii_digit())
+        .filter_map(|part| part.parse().ok())
+        .collect()
+}
+
+impl Metainfo {
+    pub fn parse(content: &str) -> Result<Self> {
+        let document = Document::parse(content)?;
+        let component = document.root_element();
+
+        let developer = child(component, "developer")
+            .and_then(|developer| child(developer, "name"))
+            .or_else(|| child(component, "developer_name"))
+            .and_then(text);
+
+        let homepage = component
+            .children()
+            .find(|n| n.has_tag_name("url") && n.attribute("type") == Some("homepage"))
+            .and_then(text);
+
+        // Releases should be newest first, but don't count on every packager getting that right
+        let version = child(component, "releases")
+            .into_iter()
+            .flat_map(|releases| releases.children())
+            .filter(|n| n.has_tag_name("release"))
+            .filter_map(|release| release.attribute("version"))
+            .max_by(|a, b| parse_version(a).cmp(&parse_version(b)))
+            .map(str::to_string);
+
+        Ok(Self {
+            id: child(component, "id").and_then(text),
+            name: child(component, "name").and_then(text),
+            summary: child(component, "summary").and_then(text),
+            developer,
+            homepage,
+            license: child(component, "project_license").and_then(text),
+            version,
+        })
+    }
+    /// Reads the first parseable metainfo file from an extracted AppImage.
+    pub async fn find(squashfs: &Path) -> Result<Option<Self>> {
+        for dir in ["usr/share/metainfo", "usr/share/appdata"] {
+            let dir = squashfs.join(dir);
+
+            if !fs::try_exists(&dir).await? {
+                continue;
+            }
+
+            let mut entries = fs::read_dir(&dir).await?;
+            while let Some(entry) = entries.next_entry().await? {
+                if entry.path().extension() != Some("xml".as_ref()) {
+                    continue;
+                }
+
+                if let Ok(metainfo) = Self::parse(&fs::read_to_string(entry.path()).await?) {
+                    return Ok(Some(metainfo));
+                }
+            }
+        }
+
+        Ok(None)
+    }
+}
@@ -1,24 +1,170 @@
-use std::path::PathBuf;
+use std::{
+    ffi::{CStr, CString, OsStr, OsString},
+    os::unix::ffi::{OsStrExt, OsStringExt},
+    path::{Path, PathBuf},
+};
 
-use crate::Result;
+use tokio::{fs, io::AsyncWriteExt};
 
-pub fn zap_rs_home() -> Result<PathBuf> {
-    let home = std::env::var("HOME")?;
-    Ok(PathBuf::from(home).join(".local/share/zap-rs"))
+use crate::{Error, Result};
+
+/// `HOME`, or the home directory in the passwd database when it's unset.
+pub fn home_dir() -> Result<PathBuf> {
+    match std::env::var_os("HOME") {
+        Some(home) if !home.is_empty() => Ok(PathBuf::from(home)),
+        _ => passwd_home().ok_or(Error::HomeNotFound),
+    }
+}
+
+fn passwd_home() -> Option<PathBuf> {
+    let mut buf = vec![0 as libc::c_char; 1024];
+
+    loop {
+        // SAFETY: passwd is plain data, getpwuid_r fills it with pointers into buf
+        let mut passwd: libc::passwd = unsafe { std::mem::zeroed() };
+        let mut result = std::ptr::null_mut();
+
+        // SAFETY: buf outlives every use of the pointers in passwd
+        let code = unsafe {
+            libc::getpwuid_r(
+                libc::getuid(),
+                &mut passwd,
+                buf.as_mut_ptr(),
+                buf.len(),
+                &mut result,
+            )
+        };
+
+        if code == libc::ERANGE && buf.len() < 1 << 20 {
+            buf.resize(buf.len() * 2, 0);
+            continue;
+        }
+        if code != 0 || result.is_null() || passwd.pw_dir.is_null() {
+            return None;
+        }
+
+        // SAFETY: pw_dir is a NUL-terminated string in buf
+        let dir = unsafe { CStr::from_ptr(passwd.pw_dir) };
+        return (!dir.is_empty()).then(|| PathBuf::from(OsStr::from_bytes(dir.to_bytes())));
+    }
+}
+
+/// Whether `path` can be written to, or created in its nearest existing parent.
+pub fn is_writable(path: &Path) -> bool {
+    let Some(existing) = path.ancestors().find(|ancestor| ancestor.exists()) else {
+        return false;
+    };
+    let Ok(existing) = CString::new(existing.as_os_str().as_bytes()) else {
+        return false;
+    };
+
+    // SAFETY: existing is a NUL-terminated string that outlives the call
+    unsafe { libc::access(existing.as_ptr(), libc::W_OK) == 0 }
 }
 
-pub fn index_dir() -> Result<PathBuf> {
-    Ok(zap_rs_home()?.join("index"))
+/// A new directory in `parent` that only the current user can get into.
+pub fn make_temp_dir(parent: &Path) -> Result<PathBuf> {
+    let template = parent.join("zap-rs-XXXXXX");
+    let template =
+        CString::new(template.into_os_string().into_vec()).map_err(|_| Error::InvalidPath)?;
+    let mut template = template.into_bytes_with_nul();
+
+    // SAFETY: template is a NUL-terminated string mkdtemp fills in place
+    if unsafe { libc::mkdtemp(template.as_mut_ptr().cast()) }.is_null() {
+        return Err(std::io::Error::last_os_error().into());
+    }
+
+    template.pop();
+    Ok(PathBuf::from(OsString::from_vec(template)))
+}
+
+/// Writes `contents` aside and renames it over `path`, so readers never see it half written.
+pub async fn write_atomically(path: &Path, contents: &[u8]) -> Result<()> {
+    write_aside(path, contents, 0o666).await
+}
+
+/// Like `write_atomically`, for files with secrets only the user may read.
+pub async fn write_private(path: &Path, contents: &[u8]) -> Result<()> {
+    write_aside(path, contents, 0o600).await
 }
 
-pub fn appimages_dir() -> Result<PathBuf> {
-    Ok(zap_rs_home()?.join("appimages"))
+async fn write_aside(path: &Path, contents: &[u8], mode: u32) -> Result<()> {
+    let file_name = path.file_name().ok_or(Error::InvalidPath)?;
+    // Named after the process so concurrent writers don't share the file
+    let mut temp_name = OsString::from(".");
+    temp_name.push(file_name);
+    temp_name.push(format!(".{}.tmp", std::process::id()));
+    let temp_path = path.with_file_name(temp_name);
+
+    // A leftover would keep its permissions, the mode only applies to new files
+    if fs::try_exists(&temp_path).await? {
+        fs::remove_file(&temp_path).await?;
+    }
+    let mut file = fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(mode)
+        .open(&temp_path)
+        .await?;
+    let written = async {
+        file.write_all(contents).await?;
+        file.sync_all().await
+    }
+    .await;
+    if let Err(e) = written {
+        fs::remove_file(&temp_path).await?;
+        return Err(e.into());
+    }
+
+    fs::rename(temp_path, path).await?;
+
+    Ok(())
 }
 
-pub fn desktops_dir() -> Result<PathBuf> {
-    Ok(zap_rs_home()?.join("desktops"))
+/// The XDG base directories, with the spec's defaults for unset or relative variables.
+#[derive(Debug, Clone, Default)]
+pub struct BaseDirs {
+    pub home: PathBuf,
+    pub data: PathBuf,
+    pub config: PathBuf,
+    pub cache: PathBuf,
+    pub state: PathBuf,
+    pub bin: PathBuf,
 }
 
-pub fn icons_dir() -> Result<PathBuf> {
-    Ok(zap_rs_home()?.join("icons"))
+impl BaseDirs {
+    pub fn from_env() -> Result<Self> {
+        let home = home_dir()?;
+        let xdg_dir = |var: &str, default: &str| match std::env::var_os(var).map(PathBuf::from) {
+            Some(path) if path.is_absolute() => path,
+            _ => home.join(default),
+        };
+
+        Ok(Self {
+            data: xdg_dir("XDG_DATA_HOME", ".local/share"),
+            config: xdg_dir("XDG_CONFIG_HOME", ".config"),
+            cache: xdg_dir("XDG_CACHE_HOME", ".cache"),
+            state: xdg_dir("XDG_STATE_HOME", ".local/state"),
+            bin: xdg_dir("XDG_BIN_HOME", ".local/bin"),
+            home,
+        })
+    }
+    /// Shared by all users, only `home` is still the user's own.
+    pub fn system(home: PathBuf) -> Self {
+        Self {
+            home,
+            data: PathBuf::from("/usr/share"),
+            config: PathBuf::from("/etc/xdg"),
+            cache: PathBuf::from("/var/cache"),
+            state: PathBuf::from("/var/lib"),
+            bin: PathBuf::from("/usr/local/bin"),
+        }
+    }
+    /// `ZAP_RS_CONFIG`, or `zap-rs/config.toml` in the config directory.
+    pub fn config_file(&self) -> PathBuf {
+        match std::env::var_os("ZAP_RS_CONFIG") {
+            Some(path) => PathBuf::from(path),
+            None => self.config.join("zap-rs/config.toml"),
+        }
+    }
 }
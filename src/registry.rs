@@ -0,0 +1,338 @@
+use futures_util::{StreamExt, TryStreamExt};
+use serde::{Deserialize, Serialize};
+use std::{
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    sync::Arc,
+};
+use tokio::fs;
+
+use crate::{Config, Error, Result, write_atomically, write_private};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Recipe {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    /// Anything `--from` accepts: a repository slug, a GitHub URL or a direct URL
+    pub source: String,
+    /// Treat `source` as a repository slug to fetch from GitHub
+    #[serde(default)]
+    pub github: bool,
+    #[serde(default)]
+    pub asset_pattern: Option<String>,
+    #[serde(default)]
+    pub executable: Option<String>,
+    #[serde(default)]
+    pub checksum: ChecksumPolicy,
+    /// Name of the registry the recipe was loaded from
+    #[serde(skip)]
+    pub registry: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChecksumPolicy {
+    #[default]
+    Skip,
+    Sha256(String),
+}
+
+impl ChecksumPolicy {
+    pub fn verify(&self, sha256: &str) -> Result<()> {
+        match self {
+            ChecksumPolicy::Skip => Ok(()),
+            ChecksumPolicy::Sha256(expected) => {
+                if expected.eq_ignore_ascii_case(sha256) {
+                    Ok(())
+                } else {
+                    Err(Error::ChecksumMismatch {
+                        expected: expected.to_string(),
+                        actual: sha256.to_string(),
+                    })
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+enum RecipeFile {
+    One(Recipe),
+    Many(Vec<Recipe>),
+}
+
+impl RecipeFile {
+    fn into_recipes(self, registry: &str) -> Vec<Recipe> {
+        let mut recipes = match self {
+            RecipeFile::One(recipe) => vec![recipe],
+            RecipeFile::Many(recipes) => recipes,
+        };
+        for recipe in &mut recipes {
+            recipe.registry = registry.to_string();
+        }
+        recipes
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RegistrySource {
+    pub name: String,
+    /// A local directory, an HTTP(S) URL to a JSON index, or a git repository
+    pub url: String,
+    /// Sent as a bearer token when fetching an HTTP index
+    #[serde(default)]
+    pub token: Option<String>,
+}
+
+#[derive(Debug)]
+enum RegistryKind {
+    Local(PathBuf),
+    Http,
+    Git(String),
+}
+
+impl RegistrySource {
+    fn kind(&self) -> RegistryKind {
+        let url = self.url.as_str();
+
+        if let Some(git_url) = url.strip_prefix("git+") {
+            RegistryKind::Git(git_url.to_string())
+        } else if url.ends_with(".git") || url.starts_with("git@") || url.starts_with("ssh://") {
+            RegistryKind::Git(url.to_string())
+        } else if url.starts_with("http://") || url.starts_with("https://") {
+            RegistryKind::Http
+        } else {
+            RegistryKind::Local(PathBuf::from(url.strip_prefix("file://").unwrap_or(url)))
+        }
+    }
+    /// Names end up as file names in the cache, so they can't point anywhere else.
+    fn validate_name(name: &str) -> Result<()> {
+        if name.is_empty() || name == "." || name == ".." || name.contains('/') {
+            return Err(Error::InvalidRegistryName(name.to_string()));
+        }
+
+        Ok(())
+    }
+    fn cache_path(&self, config: &Config) -> Result<PathBuf> {
+        let cache_dir = config.registry_cache_dir();
+        let path = match self.kind() {
+            RegistryKind::Local(path) => return Ok(path),
+            RegistryKind::Http => cache_dir.join(format!("{}.json", self.name)),
+            RegistryKind::Git(_) => cache_dir.join(&self.name),
+        };
+
+        // registries.json may have been written by hand
+        Self::validate_name(&self.name)?;
+        if path.parent() != Some(cache_dir.as_path()) {
+            return Err(Error::InvalidRegistryName(self.name.to_string()));
+        }
+
+        Ok(path)
+    }
+}
+
+#[derive(Debug)]
+pub struct Registry {
+    config: Arc<Config>,
+    client: reqwest::Client,
+}
+
+impl Registry {
+    pub fn new(config: Arc<Config>, client: reqwest::Client) -> Self {
+        Self { config, client }
+    }
+    pub async fn sources(&self) -> Result<Vec<RegistrySource>> {
+        let registries_file = self.config.registries_file();
+
+        if !fs::try_exists(&registries_file).await? {
+            return Ok(vec![]);
+        }
+
+        Ok(serde_json::from_str(
+            &fs::read_to_string(registries_file).await?,
+        )?)
+    }
+    async fn save_sources(&self, sources: &[RegistrySource]) -> Result<()> {
+        let registries_file = self.config.registries_file();
+
+        if let Some(parent) = registries_file.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+
+        // Tokens are saved along with the sources
+        let json = serde_json::to_string_pretty(sources)?;
+        write_private(&registries_file, json.as_bytes()).await?;
+
+        Ok(())
+    }
+    pub async fn add_source(&self, source: RegistrySource) -> Result<()> {
+        RegistrySource::validate_name(&source.name)?;
+
+        let mut sources = self.sources().await?;
+
+        sources.retain(|s| s.name != source.name);
+        sources.push(source);
+
+        self.save_sources(&sources).await
+    }
+    pub async fn remove_source(&self, name: &str) -> Result<()> {
+        let mut sources = self.sources().await?;
+        let source = sources
+            .iter()
+            .position(|s| s.name == name)
+            .map(|i| sources.remove(i))
+            .ok_or_else(|| Error::RegistryNotFound(name.to_string()))?;
+
+        // Local registries aren't cached, never delete the user's directory
+        if !matches!(source.kind(), RegistryKind::Local(_))
+            && let Ok(cache_path) = source.cache_path(&self.config)
+        {
+            if cache_path.is_dir() {
+                fs::remove_dir_all(cache_path).await?;
+            } else if fs::try_exists(&cache_path).await? {
+                fs::remove_file(cache_path).await?;
+            }
+        }
+
+        self.save_sources(&sources).await
+    }
+    async fn refresh_source(&self, source: &RegistrySource) -> Result<()> {
+        let cache_path = source.cache_path(&self.config)?;
+
+        match source.kind() {
+            RegistryKind::Local(_) => {}
+            RegistryKind::Http => {
+                let mut request = self.client.get(&source.url);
+                if let Some(token) = &source.token {
+                    request = request.bearer_auth(token);
+                }
+
+                let resp = request
+                    .send()
+                    .await
+                    .and_then(|resp| resp.error_for_status())
+                    .map_err(|e| Error::Download {
+                        url: source.url.to_string(),
+                        source: e,
+                    })?;
+                let body = resp.text().await?;
+
+                // Make sure we never cache something we can't read back
+                serde_json::from_str::<RecipeFile>(&body)?;
+
+                fs::create_dir_all(self.config.registry_cache_dir()).await?;
+                write_atomically(&cache_path, body.as_bytes()).await?;
+            }
+            RegistryKind::Git(url) => {
+                let status = if cache_path.join(".git").exists() {
+                    Command::new("git")
+                        .arg("-C")
+                        .arg(&cache_path)
+                        .args(["pull", "--ff-only", "--quiet"])
+                        .stdout(Stdio::null())
+                        .status()?
+                } else {
+                    fs::create_dir_all(self.config.registry_cache_dir()).await?;
+                    Command::new("git")
+                        .args(["clone", "--depth", "1", "--quiet", "--", &url])
+                        .arg(&cache_path)
+                        .stdout(Stdio::null())
+                        .status()?
+                };
+
+                if !status.success() {
+                    return Err(Error::RegistryRefresh(source.name.to_string()));
+                }
+            }
+        }
+
+        Ok(())
+    }
+    pub async fn refresh(&self) -> Result<()> {
+        futures_util::stream::iter(self.sources().await?)
+            .map(Ok)
+            .try_for_each_concurrent(
+                self.config.network.concurrency.max(1),
+                |source| async move {
+                    self.refresh_source(&source).await?;
+                    println!("Refreshed {}", source.name);
+                    Ok(())
+                },
+            )
+            .await
+    }
+    async fn load_dir(&self, dir: &Path, registry: &str) -> Result<Vec<Recipe>> {
+        let mut recipes = vec![];
+
+        for dir in [dir.to_path_buf(), dir.join("recipes")] {
+            if !fs::try_exists(&dir).await? {
+                continue;
+            }
+
+            let mut entries = fs::read_dir(&dir).await?;
+            while let Some(entry) = entries.next_entry().await? {
+                if entry.path().extension() == Some("json".as_ref()) {
+                    let content = fs::read_to_string(entry.path()).await?;
+                    let file: RecipeFile = serde_json::from_str(&content)?;
+                    recipes.extend(file.into_recipes(registry));
+                }
+            }
+        }
+
+        Ok(recipes)
+    }
+    /// Every known recipe, local ones first so they can shadow registry entries.
+    pub async fn recipes(&self) -> Result<Vec<Recipe>> {
+        let mut recipes = self.load_dir(&self.config.recipes_dir(), "local").await?;
+
+        for source in self.sources().await? {
+            let cache_path = match source.cache_path(&self.config) {
+                Ok(cache_path) => cache_path,
+                Err(e) => {
+                    eprintln!("Skipping registry {}: {e}", source.name);
+                    continue;
+                }
+            };
+
+            if !fs::try_exists(&cache_path).await?
+                && let Err(e) = self.refresh_source(&source).await
+            {
+                eprintln!("Skipping registry {}: {e}", source.name);
+                continue;
+            }
+
+            match source.kind() {
+                RegistryKind::Http => {
+                    let content = fs::read_to_string(&cache_path).await?;
+                    let file: RecipeFile = serde_json::from_str(&content)?;
+                    recipes.extend(file.into_recipes(&source.name));
+                }
+                _ => recipes.extend(self.load_dir(&cache_path, &source.name).await?),
+            }
+        }
+
+        Ok(recipes)
+    }
+    pub async fn get(&self, name: &str) -> Result<Recipe> {
+        self.recipes()
+            .await?
+            .into_iter()
+            .find(|recipe| recipe.name == name)
+            .ok_or_else(|| Error::RecipeNotFound(name.to_string()))
+    }
+    pub async fn search(&self, term: &str) -> Result<Vec<Recipe>> {
+        let term = term.to_lowercase();
+
+        Ok(self
+            .recipes()
+            .await?
+            .into_iter()
+            .filter(|recipe| {
+                recipe.name.to_lowercase().contains(&term)
+                    || recipe.description.to_lowercase().contains(&term)
+            })
+            .collect())
+    }
+}
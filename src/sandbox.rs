@@ -0,0 +1,266 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use crate::{Error, Result};
+
+/// Devices that can be let into the sandbox by name, besides plain `/dev` paths.
+pub const SANDBOX_DEVICES: [&str; 3] = ["dri", "snd", "video"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum SandboxBackend {
+    #[value(alias = "bwrap")]
+    Bubblewrap,
+    Firejail,
+}
+
+impl SandboxBackend {
+    pub fn program(&self) -> &'static str {
+        match self {
+            SandboxBackend::Bubblewrap => "bwrap",
+            SandboxBackend::Firejail => "firejail",
+        }
+    }
+}
+
+/// What a sandboxed app gets to see, everything else is left out.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SandboxProfile {
+    pub backend: SandboxBackend,
+    pub network: bool,
+    /// Read-write access to the user's home directory
+    pub home: bool,
+    #[serde(default)]
+    pub paths: Vec<PathBuf>,
+    #[serde(default)]
+    pub read_only_paths: Vec<PathBuf>,
+    /// Names from [`SANDBOX_DEVICES`] or `/dev` paths
+    #[serde(default)]
+    pub devices: Vec<String>,
+}
+
+pub fn validate_device(device: &str) -> Result<()> {
+    if SANDBOX_DEVICES.contains(&device) || device.starts_with("/dev/") {
+        Ok(())
+    } else {
+        Err(Error::InvalidSandboxDevice(device.to_string()))
+    }
+}
+
+fn device_paths(device: &str) -> Vec<PathBuf> {
+    match device {
+        "dri" => vec![PathBuf::from("/dev/dri")],
+        "snd" => vec![PathBuf::from("/dev/snd")],
+        // Cameras come and go, these are the ones around when the launcher is written
+        "video" => std::fs::read_dir("/dev")
+            .into_iter()
+            .flatten()
+            .flatten()
+            .filter(|entry| entry.file_name().to_string_lossy().starts_with("video"))
+            .map(|entry| entry.path())
+            .collect(),
+        path => vec![PathBuf::from(path)],
+    }
+}
+
+/// `XDG_RUNTIME_DIR`, or where systemd puts it when it's unset.
+fn runtime_dir() -> PathBuf {
+    match std::env::var_os("XDG_RUNTIME_DIR").map(PathBuf::from) {
+        Some(path) if path.is_absolute() => path,
+        // SAFETY: getuid can't fail
+        _ => PathBuf::from(format!("/run/user/{}", unsafe { libc::getuid() })),
+    }
+}
+
+/// The Wayland socket and X authority file, the only parts of the runtime directory
+/// a sandboxed app needs to show its windows.
+fn display_paths(runtime_dir: &Path) -> Vec<PathBuf> {
+    let wayland_display = std::env::var_os("WAYLAND_DISPLAY").unwrap_or("wayland-0".into());
+    let mut paths = vec![runtime_dir.join(wayland_display)];
+
+    if let Some(xauthority) = std::env::var_os("XAUTHORITY").map(PathBuf::from)
+        && xauthority.starts_with(runtime_dir)
+    {
+        paths.push(xauthority);
+    }
+
+    paths
+}
+
+fn bind(command: &mut Vec<String>, flag: &str, path: &Path) {
+    let path = path.to_string_lossy().to_string();
+    command.extend([flag.to_string(), path.clone(), path]);
+}
+
+fn path_arg(flag: &str, path: &Path) -> String {
+    format!("{flag}={}", path.display())
+}
+
+impl SandboxProfile {
+    /// Starts out locked down apart from the network, which most apps can't do without.
+    pub fn new(backend: SandboxBackend) -> Self {
+        Self {
+            backend,
+            network: true,
+            home: false,
+            paths: vec![],
+            read_only_paths: vec![],
+            devices: vec![],
+        }
+    }
+    /// The command line running `target` in the sandbox. `app_paths` are the app's own
+    /// files it needs to read, `data_paths` the ones it writes to, such as portable directories.
+    /// With `mount`, the AppImage is mounted before entering the sandbox if the backend can.
+    pub fn command(
+        &self,
+        home: &Path,
+        target: &Path,
+        app_paths: &[PathBuf],
+        data_paths: &[PathBuf],
+        mount: bool,
+    ) -> Vec<String> {
+        let mut command = vec![self.backend.program().to_string()];
+
+        match self.backend {
+            SandboxBackend::Bubblewrap => {
+                bind(&mut command, "--ro-bind", Path::new("/"));
+                command.extend(
+                    ["--dev", "/dev", "--proc", "/proc", "--tmpfs", "/tmp"].map(str::to_string),
+                );
+                // The display server's sockets live in the otherwise empty /tmp
+                if Path::new("/tmp/.X11-unix").exists() {
+                    bind(&mut command, "--ro-bind", Path::new("/tmp/.X11-unix"));
+                }
+
+                // The session bus, pulse and the systemd user manager live in the runtime
+                // directory and would run commands outside the sandbox if asked
+                let runtime_dir = runtime_dir();
+                command.extend([
+                    "--tmpfs".to_string(),
+                    runtime_dir.to_string_lossy().to_string(),
+                ]);
+                for path in display_paths(&runtime_dir) {
+                    bind(&mut command, "--ro-bind-try", &path);
+                }
+
+                if self.home {
+                    bind(&mut command, "--bind", home);
+                } else {
+                    command.extend(["--tmpfs".to_string(), home.to_string_lossy().to_string()]);
+                }
+
+                // Binds come after the home directory is hidden, so they show through it
+                for path in app_paths.iter().chain(&self.read_only_paths) {
+                    bind(&mut command, "--ro-bind-try", path);
+                }
+                for path in data_paths.iter().chain(&self.paths) {
+                    bind(&mut command, "--bind-try", path);
+                }
+                for device in &self.devices {
+                    for path in device_paths(device) {
+                        bind(&mut command, "--dev-bind-try", &path);
+                    }
+                }
+
+                if !self.network {
+                    command.push("--unshare-net".to_string());
+                }
+                // A new session keeps the app from pushing input into the terminal with TIOCSTI
+                command.extend(
+                    [
+                        "--unshare-pid",
+                        "--unshare-ipc",
+                        "--unshare-uts",
+                        "--new-session",
+                        "--die-with-parent",
+                        "--",
+                    ]
+                    .map(str::to_string),
+                );
+            }
+            SandboxBackend::Firejail => {
+                command.extend(["--quiet", "--noprofile", "--private-dev"].map(str::to_string));
+
+                if !self.network {
+                    command.push("--net=none".to_string());
+                }
+
+                // Whitelisting anything in the home directory hides the rest of it,
+                // paths elsewhere are visible anyway
+                if !self.home {
+                    let whitelist: Vec<&PathBuf> = app_paths
+                        .iter()
+                        .chain(data_paths)
+                        .chain(&self.paths)
+                        .chain(&self.read_only_paths)
+                        .filter(|path| path.starts_with(home))
+                        .collect();
+
+                    if whitelist.is_empty() {
+                        command.push("--private".to_string());
+                    }
+                    for path in whitelist {
+                        command.push(path_arg("--whitelist", path));
+                    }
+                }
+                for path in app_paths.iter().chain(&self.read_only_paths) {
+                    command.push(path_arg("--read-only", path));
+                }
+
+                // Firejail only knows devices by kind
+                for (device, flag) in [
+                    ("dri", "--no3d"),
+                    ("snd", "--nosound"),
+                    ("video", "--novideo"),
+                ] {
+                    if !self.devices.iter().any(|allowed| allowed == device) {
+                        command.push(flag.to_string());
+                    }
+                }
+
+                if mount {
+                    command.push("--appimage".to_string());
+                }
+            }
+        }
+
+        command.push(target.to_string_lossy().to_string());
+
+        command
+    }
+}
+
+impl fmt::Display for SandboxProfile {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let on_off = |enabled: bool| if enabled { "on" } else { "off" };
+        let join = |paths: &[PathBuf]| {
+            paths
+                .iter()
+                .map(|path| path.display().to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+
+        write!(
+            fmt,
+            "{}, network {}, home {}",
+            self.backend.program(),
+            on_off(self.network),
+            on_off(self.home)
+        )?;
+        if !self.paths.is_empty() {
+            write!(fmt, ", allows {}", join(&self.paths))?;
+        }
+        if !self.read_only_paths.is_empty() {
+            write!(fmt, ", allows reading {}", join(&self.read_only_paths))?;
+        }
+        if !self.devices.is_empty() {
+            write!(fmt, ", devices {}", self.devices.join(", "))?;
+        }
+
+        Ok(())
+    }
+}
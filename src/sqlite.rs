@@ -0,0 +1,188 @@
+use async_trait::async_trait;
+use rusqlite::{Connection, OptionalExtension, params};
+use std::{
+    path::Path,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+};
+use tokio::fs;
+
+use crate::{AppImage, Config, Error, IndexStore, JsonIndex, Result, backup_entry, parse_entry};
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS entries (
+        appname TEXT PRIMARY KEY,
+        data TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS files (
+        appname TEXT NOT NULL,
+        path TEXT NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS files_by_path ON files (path);
+    CREATE INDEX IF NOT EXISTS files_by_appname ON files (appname);
+";
+
+/// Every entry in one SQLite database, with the files each app owns for quick lookups.
+#[derive(Debug)]
+pub struct SqliteIndex {
+    config: Arc<Config>,
+    connection: Mutex<Connection>,
+}
+
+impl SqliteIndex {
+    /// Creates the database on first use, starting out with the entries of the JSON index.
+    pub async fn open(config: Arc<Config>) -> Result<Self> {
+        let path = config.index_db_file();
+        let exists = fs::try_exists(&path).await?;
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+        let connection = Connection::open(&path)?;
+        connection.execute_batch(SCHEMA)?;
+
+        let index = Self {
+            config: config.clone(),
+            connection: Mutex::new(connection),
+        };
+        if !exists {
+            let imported = index.import(&JsonIndex::new(config)).await?;
+            if imported > 0 {
+                println!("Imported {imported} entries from the JSON index.");
+            }
+        }
+
+        Ok(index)
+    }
+    fn connection(&self) -> MutexGuard<'_, Connection> {
+        self.connection
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+    fn write_entry(
+        &self,
+        connection: &Connection,
+        appimage: &AppImage,
+        appname: &str,
+    ) -> Result<()> {
+        connection.execute(
+            "INSERT INTO entries (appname, data) VALUES (?1, ?2)
+             ON CONFLICT (appname) DO UPDATE SET data = excluded.data",
+            params![appname, serde_json::to_string(appimage)?],
+        )?;
+
+        connection.execute("DELETE FROM files WHERE appname = ?1", [appname])?;
+        for file in appimage.owned_files(&self.config) {
+            connection.execute(
+                "INSERT INTO files (appname, path) VALUES (?1, ?2)",
+                params![appname, file.to_string_lossy()],
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl IndexStore for SqliteIndex {
+    async fn read(&self, appname: &str) -> Result<String> {
+        self.connection()
+            .query_row(
+                "SELECT data FROM entries WHERE appname = ?1",
+                [appname],
+                |row| row.get(0),
+            )
+            .optional()?
+            .ok_or_else(|| Error::NotFound(appname.to_string()))
+    }
+    async fn list(&self) -> Result<Vec<String>> {
+        let connection = self.connection();
+        let mut statement = connection.prepare("SELECT appname FROM entries ORDER BY appname")?;
+        let appnames = statement
+            .query_map([], |row| row.get(0))?
+            .collect::<rusqlite::Result<_>>()?;
+
+        Ok(appnames)
+    }
+    async fn exists(&self, appname: &str) -> Result<bool> {
+        Ok(self
+            .connection()
+            .query_row(
+                "SELECT 1 FROM entries WHERE appname = ?1",
+                [appname],
+                |_| Ok(()),
+            )
+            .optional()?
+            .is_some())
+    }
+    async fn add(&self, appimage: &AppImage, appname: &str) -> Result<()> {
+        if let Ok(content) = self.read(appname).await {
+            backup_entry(&self.config, appname, &content).await?;
+        }
+
+        let mut connection = self.connection();
+        let transaction = connection.transaction()?;
+        self.write_entry(&transaction, appimage, appname)?;
+        transaction.commit()?;
+
+        Ok(())
+    }
+    async fn remove(&self, appname: &str) -> Result<()> {
+        let mut connection = self.connection();
+        let transaction = connection.transaction()?;
+        transaction.execute("DELETE FROM files WHERE appname = ?1", [appname])?;
+        transaction.execute("DELETE FROM entries WHERE appname = ?1", [appname])?;
+        transaction.commit()?;
+
+        Ok(())
+    }
+    /// All at once, and without backing up the entries it replaces.
+    async fn import(&self, json: &JsonIndex) -> Result<usize> {
+        let mut entries = vec![];
+        for (appname, appimage) in json.entries().await? {
+            match appimage {
+                Ok(appimage) => entries.push((appname, appimage)),
+                Err(e) => eprintln!("Skipping {appname}: {e}"),
+            }
+        }
+
+        let mut connection = self.connection();
+        let transaction = connection.transaction()?;
+        for (appname, appimage) in &entries {
+            self.write_entry(&transaction, appimage, appname)?;
+        }
+        transaction.commit()?;
+
+        Ok(entries.len())
+    }
+    async fn entries(&self) -> Result<Vec<(String, Result<AppImage>)>> {
+        let connection = self.connection();
+        let mut statement =
+            connection.prepare("SELECT appname, data FROM entries ORDER BY appname")?;
+        let rows = statement
+            .query_map([], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        Ok(rows
+            .into_iter()
+            .map(|(appname, data)| {
+                let appimage = parse_entry(&appname, &data);
+                (appname, appimage)
+            })
+            .collect())
+    }
+    async fn owner_of(&self, path: &Path) -> Result<Option<String>> {
+        // Directories like an extracted AppDir own everything in them
+        Ok(self
+            .connection()
+            .query_row(
+                "SELECT appname FROM files
+                 WHERE path = ?1 OR substr(?1, 1, length(path) + 1) = path || '/'
+                 LIMIT 1",
+                [path.to_string_lossy()],
+                |row| row.get(0),
+            )
+            .optional()?)
+    }
+}
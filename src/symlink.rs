@@ -1,42 +1,108 @@
-use std::path::PathBuf;
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 use tokio::fs;
 
-use crate::{AppImage, Result};
+use crate::{AppImage, Config, Error, Result};
 
-#[derive(Debug, Default)]
-pub struct SymlinkManager {}
+#[derive(Debug)]
+pub struct SymlinkManager {
+    config: Arc<Config>,
+}
 
 impl SymlinkManager {
-    pub fn new() -> Self {
-        Self {}
+    pub fn new(config: Arc<Config>) -> Self {
+        Self { config }
+    }
+    pub fn symlink_path(&self, executable: &str) -> PathBuf {
+        self.config.bin_dir().join(executable)
+    }
+    /// A path is ours if it's a symlink pointing at something of the app. Recorded links
+    /// count only as long as they do, another package may have replaced them since.
+    async fn is_owned(&self, path: &Path, appimage: &AppImage) -> Result<bool> {
+        let Ok(target) = fs::read_link(path).await else {
+            return Ok(false);
+        };
+
+        Ok(appimage.link_targets(&self.config).contains(&target))
+    }
+    /// The symlinks of the app, including one made before symlinks were recorded.
+    pub async fn links(&self, appimage: &AppImage) -> Result<Vec<PathBuf>> {
+        if !appimage.symlinks.is_empty() {
+            return Ok(appimage.symlinks.clone());
+        }
+
+        let symlink_path = self.symlink_path(&appimage.executable);
+        Ok(if self.is_owned(&symlink_path, appimage).await? {
+            vec![symlink_path]
+        } else {
+            vec![]
+        })
+    }
+    /// Refuses to go near a file at a command's path that isn't the app's own, unless forced.
+    pub async fn check(&self, appimage: &AppImage, force: bool) -> Result<()> {
+        for name in appimage.commands() {
+            let symlink_path = self.symlink_path(name);
+
+            // A dangling symlink doesn't "exist", but is still in the way
+            if force
+                || fs::symlink_metadata(&symlink_path).await.is_err()
+                || self.is_owned(&symlink_path, appimage).await?
+            {
+                continue;
+            }
+
+            return Err(Error::FileCollision {
+                path: symlink_path,
+                owner: None,
+            });
+        }
+
+        Ok(())
     }
-    pub async fn remove(&self, executable: &str) -> Result<()> {
-        let home = std::env::var("HOME")?;
-        let symlink_path = PathBuf::from(home).join(".local/bin").join(executable);
+    pub async fn remove_link(&self, symlink_path: &Path, appimage: &AppImage) -> Result<()> {
+        if self.is_owned(symlink_path, appimage).await? {
+            fs::remove_file(symlink_path).await?;
+        } else if fs::symlink_metadata(symlink_path).await.is_ok() {
+            eprintln!(
+                "Leaving {} alone, it was replaced by something else",
+                symlink_path.display()
+            );
+        }
 
-        fs::remove_file(symlink_path).await?;
+        Ok(())
+    }
+    pub async fn remove(&self, appimage: &AppImage) -> Result<()> {
+        for symlink_path in self.links(appimage).await? {
+            self.remove_link(&symlink_path, appimage).await?;
+        }
 
         Ok(())
     }
-    pub async fn create(&self, appimage: &AppImage) -> Result<()> {
-        let home = std::env::var("HOME")?;
-        let local_bin = PathBuf::from(home).join(".local/bin");
+    /// Links the executable and every alias to the app's command.
+    pub async fn create(&self, appimage: &AppImage, force: bool) -> Result<Vec<PathBuf>> {
+        self.check(appimage, force).await?;
 
-        fs::create_dir_all(&local_bin).await?;
+        fs::create_dir_all(self.config.bin_dir()).await?;
 
-        let symlink_path = local_bin.join(&appimage.executable);
+        let mut symlink_paths = vec![];
 
-        #[cfg(unix)]
-        {
-            use tokio::fs;
+        for name in appimage.commands() {
+            let symlink_path = self.symlink_path(name);
 
-            if symlink_path.exists() {
-                fs::remove_file(&symlink_path).await?;
+            #[cfg(unix)]
+            {
+                if fs::symlink_metadata(&symlink_path).await.is_ok() {
+                    fs::remove_file(&symlink_path).await?;
+                }
+
+                std::os::unix::fs::symlink(appimage.command_path(&self.config), &symlink_path)?;
             }
 
-            std::os::unix::fs::symlink(&appimage.file_path, &symlink_path)?;
+            symlink_paths.push(symlink_path);
         }
 
-        Ok(())
+        Ok(symlink_paths)
     }
 }
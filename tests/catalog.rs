@@ -0,0 +1,57 @@
+use zap_rs::{parse_feed, search_entries};
+
+const FEED: &str = include_str!("fixtures/feed.json");
+
+#[test]
+fn parses_feed_with_missing_fields() {
+    let entries = parse_feed(FEED).unwrap();
+
+    assert_eq!(entries.len(), 5);
+    assert_eq!(entries[4].name, "Notes");
+    assert!(entries[4].description.is_none());
+    assert!(entries[4].links.is_none());
+}
+
+#[test]
+fn resolves_github_slugs() {
+    let entries = parse_feed(FEED).unwrap();
+    let slugs: Vec<Option<String>> = entries.iter().map(|e| e.github_slug()).collect();
+
+    assert_eq!(
+        slugs,
+        [
+            Some("KDE/krita".to_string()),
+            Some("neovim/neovim".to_string()),
+            Some("balena-io/etcher".to_string()),
+            None,
+            None,
+        ]
+    );
+}
+
+#[test]
+fn entries_without_github_source_have_no_recipe() {
+    let entries = parse_feed(FEED).unwrap();
+
+    let recipe = entries[1].to_recipe().unwrap();
+    assert_eq!(recipe.source, "neovim/neovim");
+    assert!(recipe.github);
+
+    assert!(entries[3].to_recipe().is_none());
+}
+
+#[test]
+fn searches_by_name_category_and_description() {
+    let entries = parse_feed(FEED).unwrap();
+    let names = |term: &str| -> Vec<String> {
+        search_entries(&entries, term)
+            .into_iter()
+            .map(|e| e.name.to_string())
+            .collect()
+    };
+
+    assert_eq!(names("nvim").first().map(String::as_str), Some("Neovim"));
+    assert_eq!(names("graphics"), ["Inkscape", "Krita"]);
+    assert_eq!(names("usb drives"), ["balenaEtcher"]);
+    assert!(names("zzzz").is_empty());
+}
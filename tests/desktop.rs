@@ -0,0 +1,128 @@
+use zap_rs::{DesktopEntry, build_exec, escape_value, parse_exec, unescape_value};
+
+const ENTRY: &str = "\
+# Written by hand
+[Desktop Entry]
+Type=Application
+Name=Code
+Name[de]=Code-Editor
+Name[de_DE@euro]=Code-Editor (Euro)
+Comment=Edit\\stext\\nand more
+Exec=/usr/bin/code --new-window %F
+Actions=new-empty-window;
+
+[Desktop Action new-empty-window]
+Name=New Empty Window
+Exec=\"/opt/my apps/code\" --new-window
+";
+
+fn args(args: &[&str]) -> Vec<String> {
+    args.iter().map(|arg| arg.to_string()).collect()
+}
+
+#[test]
+fn unescapes_and_escapes_values() {
+    assert_eq!(unescape_value(r"a\sb\nc\td\\e"), "a b\nc\td\\e");
+    // List separators are left for the caller
+    assert_eq!(unescape_value(r"one\;two;"), r"one\;two;");
+
+    assert_eq!(escape_value(" leading"), r"\sleading");
+    assert_eq!(escape_value("a b\\c\n"), r"a b\\c\n");
+    assert_eq!(
+        unescape_value(&escape_value(" tab\there\\")),
+        " tab\there\\"
+    );
+}
+
+#[test]
+fn parses_exec_quoting() {
+    assert_eq!(
+        parse_exec(r#"code --new-window %F"#).unwrap(),
+        args(&["code", "--new-window", "%F"])
+    );
+    assert_eq!(
+        parse_exec(r#""/opt/my apps/code" "say \"hi\"" "\$HOME" """#).unwrap(),
+        args(&["/opt/my apps/code", r#"say "hi""#, "$HOME", ""])
+    );
+    assert!(parse_exec(r#""/opt/code --flag"#).is_err());
+}
+
+#[test]
+fn builds_exec_that_parses_back() {
+    let exec_args = args(&["/opt/my apps/code", r#"a"b"#, "$HOME", "plain", "%U", ""]);
+    let exec = build_exec(&exec_args);
+
+    assert_eq!(exec, r#""/opt/my apps/code" "a\"b" "\$HOME" plain %U """#);
+    assert_eq!(parse_exec(&exec).unwrap(), exec_args);
+}
+
+#[test]
+fn looks_up_localized_keys() {
+    let entry = DesktopEntry::parse(ENTRY).unwrap();
+    let main = entry.main_group().unwrap();
+
+    assert_eq!(main.get("Name").as_deref(), Some("Code"));
+    assert_eq!(
+        main.get_localized("Name", "de_DE@euro").as_deref(),
+        Some("Code-Editor (Euro)")
+    );
+    assert_eq!(
+        main.get_localized("Name", "de_AT").as_deref(),
+        Some("Code-Editor")
+    );
+    assert_eq!(main.get_localized("Name", "fr_FR").as_deref(), Some("Code"));
+    assert_eq!(main.get("Comment").as_deref(), Some("Edit text\nand more"));
+}
+
+#[test]
+fn round_trips_untouched_files() {
+    let entry = DesktopEntry::parse(ENTRY).unwrap();
+    assert_eq!(entry.to_string(), ENTRY);
+
+    let crlf = ENTRY.replace('\n', "\r\n");
+    assert_eq!(DesktopEntry::parse(&crlf).unwrap().to_string(), crlf);
+
+    let no_trailing_newline = ENTRY.trim_end();
+    assert_eq!(
+        DesktopEntry::parse(no_trailing_newline)
+            .unwrap()
+            .to_string(),
+        no_trailing_newline
+    );
+}
+
+#[test]
+fn only_rewrites_modified_lines() {
+    let crlf = ENTRY.replace('\n', "\r\n");
+    let mut entry = DesktopEntry::parse(&crlf).unwrap();
+
+    for group in entry.groups_mut() {
+        group.set_exec_program("/home/me/.local/bin/code").unwrap();
+    }
+    entry.validate().unwrap();
+
+    let expected = crlf
+        .replace(
+            "Exec=/usr/bin/code --new-window %F",
+            "Exec=/home/me/.local/bin/code --new-window %F",
+        )
+        .replace(
+            "Exec=\"/opt/my apps/code\" --new-window",
+            "Exec=/home/me/.local/bin/code --new-window",
+        );
+    assert_eq!(entry.to_string(), expected);
+}
+
+#[test]
+fn rejects_invalid_entries() {
+    assert!(DesktopEntry::parse("[Desktop Entry]\nType=Application\n[Desktop Entry]\n").is_err());
+    assert!(DesktopEntry::parse("Name=No group\n").is_err());
+
+    let missing_action = ENTRY.replace("Actions=new-empty-window;", "Actions=other;");
+    assert!(
+        DesktopEntry::parse(&missing_action)
+            .unwrap()
+            .validate()
+            .is_err()
+    );
+}
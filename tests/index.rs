@@ -0,0 +1,137 @@
+use std::{path::PathBuf, sync::Arc};
+
+use zap_rs::{
+    AppImage, BaseDirs, Config, Error, IndexStore, JsonIndex, SqliteIndex, open_index, parse_entry,
+};
+
+fn config(name: &str, backend: &str) -> Arc<Config> {
+    let root = std::env::temp_dir().join(format!("zap-rs-test-{}-{name}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&root);
+
+    let dirs = BaseDirs {
+        home: root.clone(),
+        data: root.join("data"),
+        config: root.join("config"),
+        cache: root.join("cache"),
+        state: root.join("state"),
+        bin: root.join("bin"),
+    };
+
+    Arc::new(Config::parse(&format!("[index]\nbackend = \"{backend}\""), dirs).unwrap())
+}
+
+fn entry(config: &Config, appname: &str, run_mode: &str) -> AppImage {
+    let file_path = config.appimages_dir().join(format!("{appname}.AppImage"));
+    let content = format!(
+        r#"{{
+            "schema_version": 1,
+            "file_path": "{}",
+            "executable": "{appname}",
+            "source": {{ "identifier": "raw_url", "meta": {{ "url": "https://example.com/{appname}.AppImage" }} }},
+            "run_mode": "{run_mode}"
+        }}"#,
+        file_path.display()
+    );
+
+    parse_entry(appname, &content).unwrap()
+}
+
+#[tokio::test]
+async fn sqlite_index_round_trips_entries() {
+    let config = config("round-trip", "sqlite");
+    let index = SqliteIndex::open(config.clone()).await.unwrap();
+
+    index
+        .add(&entry(&config, "nvim", "fuse"), "nvim")
+        .await
+        .unwrap();
+    index
+        .add(&entry(&config, "codium", "fuse"), "codium")
+        .await
+        .unwrap();
+
+    assert_eq!(index.list().await.unwrap(), ["codium", "nvim"]);
+    assert!(index.exists("nvim").await.unwrap());
+    assert_eq!(index.get("nvim").await.unwrap().executable, "nvim");
+
+    index.remove("nvim").await.unwrap();
+    assert!(!index.exists("nvim").await.unwrap());
+    assert!(index.get("nvim").await.is_err());
+}
+
+#[tokio::test]
+async fn sqlite_owner_of_matches_files_and_directory_contents() {
+    let config = config("owner-of", "sqlite");
+    let index = SqliteIndex::open(config.clone()).await.unwrap();
+
+    let appimage = entry(&config, "code", "extract");
+    index.add(&appimage, "code").await.unwrap();
+    let appdir = appimage.appdir_path(&config);
+
+    let file_owner = index.owner_of(&appimage.file_path).await.unwrap();
+    assert_eq!(file_owner.as_deref(), Some("code"));
+
+    let content_owner = index.owner_of(&appdir.join("usr/bin/code")).await.unwrap();
+    assert_eq!(content_owner.as_deref(), Some("code"));
+
+    // Sharing a prefix isn't being inside the directory
+    let mut sibling = appdir.into_os_string();
+    sibling.push("-insiders");
+    assert_eq!(index.owner_of(&PathBuf::from(sibling)).await.unwrap(), None);
+    assert_eq!(
+        index
+            .owner_of(&PathBuf::from("/usr/bin/code"))
+            .await
+            .unwrap(),
+        None
+    );
+}
+
+#[tokio::test]
+async fn sqlite_index_imports_the_json_index_when_created() {
+    let config = config("import", "sqlite");
+    let json = JsonIndex::new(config.clone());
+
+    json.add(&entry(&config, "nvim", "fuse"), "nvim")
+        .await
+        .unwrap();
+    json.add(&entry(&config, "krita", "fuse"), "krita")
+        .await
+        .unwrap();
+    std::fs::write(config.index_dir().join("broken.json"), "{").unwrap();
+
+    let index = SqliteIndex::open(config.clone()).await.unwrap();
+
+    // Unreadable entries are skipped rather than failing the import
+    assert_eq!(index.list().await.unwrap(), ["krita", "nvim"]);
+    assert_eq!(index.import(&json).await.unwrap(), 2);
+    assert_eq!(index.list().await.unwrap(), ["krita", "nvim"]);
+}
+
+#[tokio::test]
+async fn database_is_only_created_when_asked_to() {
+    let config = config("create", "sqlite");
+    JsonIndex::new(config.clone())
+        .add(&entry(&config, "nvim", "fuse"), "nvim")
+        .await
+        .unwrap();
+
+    let index = open_index(config.clone(), false).await.unwrap();
+    assert_eq!(index.list().await.unwrap(), ["nvim"]);
+    assert!(!config.index_db_file().exists());
+
+    let index = open_index(config.clone(), true).await.unwrap();
+    assert_eq!(index.list().await.unwrap(), ["nvim"]);
+    assert!(config.index_db_file().exists());
+}
+
+#[test]
+fn refuses_schema_versions_past_u32() {
+    let content = r#"{ "schema_version": 4294967296, "file_path": "/x", "executable": "x",
+        "source": { "identifier": "raw_url", "meta": { "url": "https://example.com/x" } } }"#;
+
+    assert!(matches!(
+        parse_entry("x", content),
+        Err(Error::IndexTooNew { .. })
+    ));
+}